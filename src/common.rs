@@ -1,32 +1,105 @@
-use std::fs::read_to_string;
-use std::io::Error as StdIOError;
+use std::fs::{read_to_string, File};
+use std::io::{BufRead, BufReader, Error as StdIOError, Lines};
+
+pub mod args;
+pub mod grid;
+pub mod interval;
+pub mod point;
+pub mod random;
+pub mod log;
+pub mod normalize;
+pub mod parser;
+pub mod regex_parse;
+
+pub use args::ToolArgs;
+pub use grid::Grid;
+pub use interval::Interval;
+pub use normalize::{LineNormalizer, Normalization};
+pub use point::{Direction, Point, Vec2};
 
 #[derive(Debug)]
+#[allow(dead_code, clippy::enum_variant_names)]
 pub enum Error {
     General(String),
-    IOError(StdIOError)
+    IOError(StdIOError),
+    Parse { pos: usize, message: String },
 }
 
 pub fn read_lines(input_path: &str) -> Result<Vec<String>, Error> {
+    read_lines_with(input_path, &Normalization::STANDARD)
+}
+
+pub fn read_lines_with(input_path: &str, normalization: &Normalization) -> Result<Vec<String>, Error> {
     match read_to_string(input_path) {
         Err(e) => Err(Error::IOError(e)),
         Ok(s) => {
-            Ok(s.replace("\r", "")
-                .split("\n")
-                .into_iter()
-                .map(|s| s.to_string())
-                .collect()
-            )
+            let (lines, applied) = normalize::normalize(&s, normalization);
+            log::debug!("Read {} lines from {}", lines.len(), input_path);
+            if !applied.is_empty() {
+                log::warning!("Normalized {}: {}", input_path, applied.join("; "));
+            }
+            Ok(lines)
         }
     }
 }
 
 pub fn read_non_empty_lines(input_path: &str) -> Result<Vec<String>, Error> {
+    read_non_empty_lines_with(input_path, &Normalization::STANDARD)
+}
+
+pub fn read_non_empty_lines_with(input_path: &str, normalization: &Normalization) -> Result<Vec<String>, Error> {
     let mut result: Vec<String> = Vec::new();
-    for line in read_lines(input_path)? {
-        if line.len() > 0 {
+    for line in read_lines_with(input_path, normalization)? {
+        if !line.is_empty() {
             result.push(line);
         }
     }
     Ok(result)
-}  
\ No newline at end of file
+}
+
+// Streams the normalized lines of a reader without loading it all. The steps
+// that changed something are reported once the last line has been read.
+pub struct NormalizedLines<R: BufRead> {
+    name: String,
+    lines: Lines<R>,
+    normalizer: LineNormalizer,
+    reported: bool,
+}
+
+impl<R: BufRead> NormalizedLines<R> {
+    pub fn new(name: &str, input: R, normalization: &Normalization) -> NormalizedLines<R> {
+        NormalizedLines {
+            name: name.to_string(),
+            lines: input.lines(),
+            normalizer: LineNormalizer::new(normalization),
+            reported: false,
+        }
+    }
+}
+
+impl<R: BufRead> Iterator for NormalizedLines<R> {
+    type Item = Result<String, Error>;
+
+    fn next(&mut self) -> Option<Result<String, Error>> {
+        match self.lines.next() {
+            Some(Err(e)) => Some(Err(Error::IOError(e))),
+            Some(Ok(line)) => Some(Ok(self.normalizer.line(&line))),
+            None => {
+                let applied = self.normalizer.applied();
+                if !self.reported {
+                    log::debug!("Read {} lines from {}", self.normalizer.line_count(), self.name);
+                    if !applied.is_empty() {
+                        log::warning!("Normalized {}: {}", self.name, applied.join("; "));
+                    }
+                }
+                self.reported = true;
+                None
+            }
+        }
+    }
+}
+
+pub fn stream_lines(input_path: &str) -> Result<NormalizedLines<BufReader<File>>, Error> {
+    let file = File::open(input_path).map_err(Error::IOError)?;
+    Ok(NormalizedLines::new(input_path, BufReader::new(file), &Normalization::STANDARD))
+}
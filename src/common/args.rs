@@ -0,0 +1,78 @@
+use std::collections::{HashMap, HashSet};
+use std::str::FromStr;
+
+use crate::common::Error;
+
+// Arguments passed to a day's tool: positional values, --flags and
+// --options that take a value (`--delay 100` or `--delay=100`).
+#[derive(Debug, Default)]
+pub struct ToolArgs {
+    positional: Vec<String>,
+    flags: HashSet<String>,
+    options: HashMap<String, String>,
+}
+
+impl ToolArgs {
+    pub fn parse(args: &[String], flags: &[&str], options: &[&str]) -> Result<ToolArgs, Error> {
+        let mut tool_args = ToolArgs::default();
+        let mut iter = args.iter();
+
+        while let Some(arg) = iter.next() {
+            if !arg.starts_with("--") {
+                tool_args.positional.push(arg.clone());
+                continue;
+            }
+
+            let (name, inline_value) = match arg.split_once('=') {
+                Some((name, value)) => (name, Some(value.to_string())),
+                None => (arg.as_str(), None)
+            };
+
+            if flags.contains(&name) && inline_value.is_none() {
+                tool_args.flags.insert(name.to_string());
+            }
+            else if options.contains(&name) {
+                let value = match inline_value {
+                    Some(value) => value,
+                    None => iter
+                        .next()
+                        .cloned()
+                        .ok_or_else(|| Error::General(format!("Missing value for {}", name)))?
+                };
+                tool_args.options.insert(name.to_string(), value);
+            }
+            else {
+                let mut known: Vec<&str> = flags.iter().chain(options.iter()).copied().collect();
+                known.sort_unstable();
+                return Err(Error::General(format!("Unknown option: {} (known: {})", arg, known.join(", "))));
+            }
+        }
+
+        Ok(tool_args)
+    }
+
+    pub fn flag(&self, name: &str) -> bool {
+        self.flags.contains(name)
+    }
+
+    pub fn option(&self, name: &str) -> Option<&str> {
+        self.options.get(name).map(|s| s.as_str())
+    }
+
+    pub fn option_parsed<T: FromStr>(&self, name: &str, default: T) -> Result<T, Error> {
+        match self.option(name) {
+            None => Ok(default),
+            Some(value) => value
+                .parse::<T>()
+                .map_err(|_| Error::General(format!("Invalid value for {}: {}", name, value)))
+        }
+    }
+
+    pub fn positional(&self, idx: usize) -> Option<&str> {
+        self.positional.get(idx).map(|s| s.as_str())
+    }
+
+    pub fn positional_or<'a>(&'a self, idx: usize, default: &'a str) -> &'a str {
+        self.positional(idx).unwrap_or(default)
+    }
+}
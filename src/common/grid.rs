@@ -0,0 +1,180 @@
+use std::fmt::{Display, Formatter, Result as FmtResult};
+
+use crate::common::Error;
+use crate::common::point::{Direction, Point, Vec2};
+
+// A dense, row-major 2D grid. Positions are (x, y) with (0, 0) in the top left.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Grid<T> {
+    width: usize,
+    height: usize,
+    cells: Vec<T>,
+}
+
+impl<T: Clone> Grid<T> {
+    pub fn new(width: usize, height: usize, fill: T) -> Grid<T> {
+        Grid {
+            width,
+            height,
+            cells: vec![fill; width * height],
+        }
+    }
+}
+
+impl<T> Grid<T> {
+    pub fn from_rows(rows: Vec<Vec<T>>) -> Result<Grid<T>, Error> {
+        let height = rows.len();
+        let width = rows.first().map_or(0, |r| r.len());
+        let mut cells: Vec<T> = Vec::with_capacity(width * height);
+
+        for (y, row) in rows.into_iter().enumerate() {
+            if row.len() != width {
+                return Err(Error::General(format!(
+                    "Row {} has a mismatched length: {} (expected {})", y, row.len(), width)));
+            }
+            cells.extend(row);
+        }
+
+        Ok(Grid { width, height, cells })
+    }
+
+    // Builds a grid from a character map, one line per row.
+    pub fn parse<S, F>(lines: &[S], cell_parser: F) -> Result<Grid<T>, Error>
+        where S: AsRef<str>, F: Fn(char) -> Result<T, Error>
+    {
+        let mut rows: Vec<Vec<T>> = Vec::new();
+
+        for line in lines {
+            let row = line
+                .as_ref()
+                .chars()
+                .map(&cell_parser)
+                .collect::<Result<Vec<T>, Error>>()?;
+            rows.push(row);
+        }
+
+        Grid::from_rows(rows)
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn is_in_bounds(&self, pos: Point) -> bool {
+        pos.x >= 0 && (pos.x as usize) < self.width && pos.y >= 0 && (pos.y as usize) < self.height
+    }
+
+    fn index(&self, pos: Point) -> Option<usize> {
+        if self.is_in_bounds(pos) {
+            Some(pos.y as usize * self.width + pos.x as usize)
+        }
+        else {
+            None
+        }
+    }
+
+    pub fn get(&self, pos: Point) -> Option<&T> {
+        self.index(pos).map(|idx| &self.cells[idx])
+    }
+
+    pub fn get_mut(&mut self, pos: Point) -> Option<&mut T> {
+        self.index(pos).map(|idx| &mut self.cells[idx])
+    }
+
+    // Returns false if the position is out of bounds.
+    pub fn set(&mut self, pos: Point, value: T) -> bool {
+        match self.get_mut(pos) {
+            Some(cell) => {
+                *cell = value;
+                true
+            },
+            None => false
+        }
+    }
+
+    pub fn positions(&self) -> impl Iterator<Item = Point> {
+        let width = self.width as i32;
+        let height = self.height as i32;
+        (0..height).flat_map(move |y| (0..width).map(move |x| Point::new(x, y)))
+    }
+
+    #[allow(dead_code)]
+    pub fn iter(&self) -> impl Iterator<Item = (Point, &T)> {
+        self.positions().zip(self.cells.iter())
+    }
+
+    #[allow(dead_code)]
+    pub fn neighbours4(&self, pos: Point) -> impl Iterator<Item = Point> + '_ {
+        Direction::ALL
+            .iter()
+            .map(move |d| pos + d.get_movement_vector())
+            .filter(move |p| self.is_in_bounds(*p))
+    }
+
+    #[allow(dead_code)]
+    pub fn neighbours8(&self, pos: Point) -> impl Iterator<Item = Point> + '_ {
+        const NEIGHBOURS_8: [Vec2; 8] = [
+            Point::new(-1, -1), Point::new(0, -1), Point::new(1, -1),
+            Point::new(-1, 0), Point::new(1, 0),
+            Point::new(-1, 1), Point::new(0, 1), Point::new(1, 1),
+        ];
+
+        NEIGHBOURS_8
+            .iter()
+            .map(move |d| pos + *d)
+            .filter(move |p| self.is_in_bounds(*p))
+    }
+
+    // Walks from pos (exclusive) in steps of move_vec until leaving the grid.
+    pub fn ray(&self, pos: Point, move_vec: Vec2) -> impl Iterator<Item = (Point, &T)> + '_ {
+        let mut current_pos = pos;
+        std::iter::from_fn(move || {
+            if move_vec == Point::ORIGIN {
+                return None;
+            }
+            current_pos += move_vec;
+            self.get(current_pos).map(|v| (current_pos, v))
+        })
+    }
+
+    #[allow(dead_code)]
+    pub fn row(&self, y: usize) -> Option<&[T]> {
+        if y < self.height {
+            Some(&self.cells[y * self.width .. (y + 1) * self.width])
+        }
+        else {
+            None
+        }
+    }
+
+    pub fn rows(&self) -> impl Iterator<Item = &[T]> {
+        (0..self.height).map(move |y| &self.cells[y * self.width .. (y + 1) * self.width])
+    }
+
+    #[allow(dead_code)]
+    pub fn column(&self, x: usize) -> Option<impl Iterator<Item = &T>> {
+        if x < self.width {
+            Some(self.cells.iter().skip(x).step_by(self.width))
+        }
+        else {
+            None
+        }
+    }
+}
+
+// Renders each row on its own line, cells concatenated.
+impl<T: Display> Display for Grid<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        for row in self.rows() {
+            for cell in row {
+                write!(f, "{}", cell)?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
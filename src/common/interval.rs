@@ -0,0 +1,180 @@
+use std::fmt::{Display, Formatter, Result as FmtResult};
+
+use crate::common::Error;
+
+// A non-empty, closed range of integers: start..=end.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Interval {
+    start: i64,
+    end: i64,
+}
+
+impl Interval {
+    pub fn new(start: i64, end: i64) -> Result<Interval, Error> {
+        if start <= end {
+            Ok(Interval { start, end })
+        }
+        else {
+            Err(Error::General(format!("Invalid interval: {}-{}", start, end)))
+        }
+    }
+
+    pub fn start(&self) -> i64 {
+        self.start
+    }
+
+    pub fn end(&self) -> i64 {
+        self.end
+    }
+
+    // Wider than i64 so that the full i64::MIN..=i64::MAX range fits.
+    pub fn len(&self) -> u128 {
+        self.end.abs_diff(self.start) as u128 + 1
+    }
+
+    pub fn contains(&self, n: i64) -> bool {
+        self.start <= n && n <= self.end
+    }
+
+    pub fn contains_interval(&self, other: &Interval) -> bool {
+        self.start <= other.start && other.end <= self.end
+    }
+
+    pub fn overlaps(&self, other: &Interval) -> bool {
+        self.start <= other.end && other.start <= self.end
+    }
+
+    // Overlapping or directly next to each other, so the union is one interval.
+    pub fn touches(&self, other: &Interval) -> bool {
+        self.start <= other.end.saturating_add(1) && other.start <= self.end.saturating_add(1)
+    }
+
+    pub fn intersection(&self, other: &Interval) -> Option<Interval> {
+        if self.overlaps(other) {
+            Some(Interval { start: self.start.max(other.start), end: self.end.min(other.end) })
+        }
+        else {
+            None
+        }
+    }
+
+    // One interval when they touch, otherwise both in order.
+    pub fn union(&self, other: &Interval) -> Vec<Interval> {
+        merge(&[*self, *other])
+    }
+
+    // The parts of self not covered by other, in order.
+    #[allow(dead_code)]
+    pub fn difference(&self, other: &Interval) -> Vec<Interval> {
+        if !self.overlaps(other) {
+            return vec![*self];
+        }
+
+        let mut parts: Vec<Interval> = Vec::new();
+        if self.start < other.start {
+            parts.push(Interval { start: self.start, end: other.start - 1 });
+        }
+        if other.end < self.end {
+            parts.push(Interval { start: other.end + 1, end: self.end });
+        }
+        parts
+    }
+}
+
+impl Display for Interval {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        write!(f, "{}-{}", self.start, self.end)
+    }
+}
+
+// Sorts intervals and joins the ones that overlap or are adjacent.
+pub fn merge(intervals: &[Interval]) -> Vec<Interval> {
+    let mut sorted = intervals.to_vec();
+    sorted.sort_unstable();
+
+    let mut merged: Vec<Interval> = Vec::new();
+    for interval in sorted {
+        match merged.last_mut() {
+            Some(last) if last.touches(&interval) => {
+                last.end = last.end.max(interval.end);
+            },
+            _ => merged.push(interval)
+        }
+    }
+    merged
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+
+    use super::*;
+    use crate::common::random::{Rng, check_property};
+
+    fn points(intervals: &[Interval]) -> BTreeSet<i64> {
+        intervals.iter().flat_map(|i| i.start..=i.end).collect()
+    }
+
+    fn is_normalized(intervals: &[Interval]) -> bool {
+        intervals.windows(2).all(|w| w[0].end.saturating_add(1) < w[1].start)
+    }
+
+    fn random_interval(rng: &mut Rng) -> Interval {
+        let a = rng.range(-20, 20);
+        let b = rng.range(-20, 20);
+        Interval { start: a.min(b), end: a.max(b) }
+    }
+
+    fn check_case(a: Interval, b: Interval, list: &[Interval]) -> Result<(), String> {
+        let sa = points(&[a]);
+        let sb = points(&[b]);
+        let check = |ok: bool, what: &str| if ok { Ok(()) } else { Err(String::from(what)) };
+
+        check(a.len() == sa.len() as u128, "len")?;
+        for n in -22..=22 {
+            check(a.contains(n) == sa.contains(&n), "contains")?;
+        }
+        check(a.contains_interval(&b) == sb.is_subset(&sa), "contains_interval")?;
+        check(a.overlaps(&b) != sa.is_disjoint(&sb), "overlaps")?;
+
+        let intersection: BTreeSet<i64> = sa.intersection(&sb).copied().collect();
+        let i = a.intersection(&b);
+        check(points(i.as_slice()) == intersection, "intersection")?;
+
+        let union = a.union(&b);
+        check(points(&union) == sa.union(&sb).copied().collect(), "union")?;
+        check(is_normalized(&union), "union is normalized")?;
+
+        let difference = a.difference(&b);
+        check(points(&difference) == sa.difference(&sb).copied().collect(), "difference")?;
+        check(is_normalized(&difference), "difference is normalized")?;
+
+        let merged = merge(list);
+        check(points(&merged) == points(list), "merge")?;
+        check(is_normalized(&merged), "merge is normalized")?;
+
+        Ok(())
+    }
+
+    // Compares every operation against a brute-force model built from sets of
+    // points, over randomly generated intervals.
+    #[test]
+    fn matches_set_model() {
+        check_property("Interval set model", 500, |rng| {
+            let a = random_interval(rng);
+            let b = random_interval(rng);
+            let list: Vec<Interval> = (0..rng.below(8)).map(|_| random_interval(rng)).collect();
+
+            check_case(a, b, &list).map_err(|what| {
+                let list_desc: Vec<String> = list.iter().map(|i| i.to_string()).collect();
+                format!("property '{}' with a={} b={} list=[{}]", what, a, b, list_desc.join(", "))
+            })
+        });
+    }
+
+    #[test]
+    fn len_of_full_range() {
+        let full = Interval::new(i64::MIN, i64::MAX).unwrap();
+        assert_eq!(full.len(), u64::MAX as u128 + 1);
+    }
+}
@@ -0,0 +1,145 @@
+use std::cell::RefCell;
+use std::fmt::Arguments;
+use std::sync::atomic::{AtomicU8, Ordering};
+
+// Diagnostics go to stderr so that stdout stays a clean CSV of answers.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Level {
+    Error = 0,
+    Warning = 1,
+    Info = 2,
+    Debug = 3,
+}
+
+impl Level {
+    fn from_u8(n: u8) -> Level {
+        match n {
+            0 => Level::Error,
+            1 => Level::Warning,
+            2 => Level::Info,
+            _ => Level::Debug,
+        }
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            Level::Error => "ERROR",
+            Level::Warning => "WARNING",
+            Level::Info => "INFO",
+            Level::Debug => "DEBUG",
+        }
+    }
+
+    pub fn more_verbose(self) -> Level {
+        Level::from_u8(self as u8 + 1)
+    }
+}
+
+static LEVEL: AtomicU8 = AtomicU8::new(Level::Warning as u8);
+
+pub fn set_level(level: Level) {
+    LEVEL.store(level as u8, Ordering::Relaxed);
+}
+
+pub fn level() -> Level {
+    Level::from_u8(LEVEL.load(Ordering::Relaxed))
+}
+
+pub fn enabled(level: Level) -> bool {
+    level <= self::level()
+}
+
+#[derive(Debug, Clone, Default)]
+struct Context {
+    day: Option<String>,
+    part: Option<String>,
+    line: Option<usize>,
+}
+
+thread_local! {
+    static CONTEXT: RefCell<Context> = RefCell::new(Context::default());
+}
+
+// Restores the previous context when dropped.
+pub struct ContextGuard {
+    previous: Context,
+}
+
+impl Drop for ContextGuard {
+    fn drop(&mut self) {
+        let previous = std::mem::take(&mut self.previous);
+        CONTEXT.with(|c| *c.borrow_mut() = previous);
+    }
+}
+
+fn push_context(update: impl FnOnce(&mut Context)) -> ContextGuard {
+    CONTEXT.with(|c| {
+        let previous = c.borrow().clone();
+        update(&mut c.borrow_mut());
+        ContextGuard { previous }
+    })
+}
+
+// Tags diagnostics with the day and part being solved.
+pub fn solver_context(day: &str, part: &str) -> ContextGuard {
+    push_context(|ctx| {
+        ctx.day = Some(String::from(day));
+        ctx.part = Some(String::from(part));
+        ctx.line = None;
+    })
+}
+
+// Tags diagnostics with the (1-based) input line being processed.
+pub fn line_context(line: usize) -> ContextGuard {
+    push_context(|ctx| ctx.line = Some(line))
+}
+
+fn describe_context(ctx: &Context) -> String {
+    let mut parts: Vec<String> = Vec::new();
+    if let Some(day) = &ctx.day {
+        parts.push(format!("day {}", day));
+    }
+    if let Some(part) = &ctx.part {
+        parts.push(format!("part {}", part));
+    }
+    if let Some(line) = ctx.line {
+        parts.push(format!("line {}", line));
+    }
+    parts.join(" ")
+}
+
+pub fn write(level: Level, args: Arguments) {
+    if !enabled(level) {
+        return;
+    }
+
+    let context = CONTEXT.with(|c| describe_context(&c.borrow()));
+    if context.is_empty() {
+        eprintln!("[{}] {}", level.label(), args);
+    }
+    else {
+        eprintln!("[{}] {}: {}", level.label(), context, args);
+    }
+}
+
+macro_rules! error {
+    ($($arg:tt)*) => { $crate::common::log::write($crate::common::log::Level::Error, format_args!($($arg)*)) };
+}
+
+macro_rules! warning {
+    ($($arg:tt)*) => { $crate::common::log::write($crate::common::log::Level::Warning, format_args!($($arg)*)) };
+}
+
+macro_rules! info {
+    ($($arg:tt)*) => { $crate::common::log::write($crate::common::log::Level::Info, format_args!($($arg)*)) };
+}
+
+macro_rules! debug {
+    ($($arg:tt)*) => { $crate::common::log::write($crate::common::log::Level::Debug, format_args!($($arg)*)) };
+}
+
+pub(crate) use error;
+pub(crate) use warning;
+pub(crate) use info;
+pub(crate) use debug;
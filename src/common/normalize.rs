@@ -0,0 +1,143 @@
+// Cleans up raw puzzle input before it is split into lines. Each day can pick
+// the steps that are safe for its format; every step that actually changed
+// something is reported so surprising input doesn't go unnoticed.
+// Windows line endings are always converted and are not reported.
+
+#[derive(Debug, Clone, Copy)]
+pub struct Normalization {
+    pub strip_bom: bool,
+    pub trim_trailing_whitespace: bool,
+    // Expand tabs to the next multiple of this many columns.
+    pub tab_width: Option<usize>,
+    pub ensure_final_newline: bool,
+}
+
+impl Normalization {
+    pub const STANDARD: Normalization = Normalization {
+        strip_bom: true,
+        trim_trailing_whitespace: true,
+        tab_width: Some(4),
+        ensure_final_newline: true,
+    };
+}
+
+impl Default for Normalization {
+    fn default() -> Normalization {
+        Normalization::STANDARD
+    }
+}
+
+const BOM: char = '\u{feff}';
+
+fn expand_tabs(line: &str, tab_width: usize) -> String {
+    let mut expanded = String::with_capacity(line.len());
+    let mut column = 0;
+
+    for c in line.chars() {
+        if c == '\t' {
+            let spaces = tab_width - column % tab_width;
+            expanded.extend(std::iter::repeat_n(' ', spaces));
+            column += spaces;
+        }
+        else {
+            expanded.push(c);
+            column += 1;
+        }
+    }
+
+    expanded
+}
+
+fn describe_lines(action: &str, line_numbers: &[usize]) -> Option<String> {
+    line_numbers.first().map(|first| {
+        format!("{} on {} line(s) (first at line {})", action, line_numbers.len(), first)
+    })
+}
+
+// Applies the per-line steps one line at a time and remembers which lines
+// they changed. Line endings are expected to be split off already.
+pub struct LineNormalizer {
+    normalization: Normalization,
+    line_number: usize,
+    stripped_bom: bool,
+    trimmed_lines: Vec<usize>,
+    expanded_lines: Vec<usize>,
+}
+
+impl LineNormalizer {
+    pub fn new(normalization: &Normalization) -> LineNormalizer {
+        LineNormalizer {
+            normalization: *normalization,
+            line_number: 0,
+            stripped_bom: false,
+            trimmed_lines: Vec::new(),
+            expanded_lines: Vec::new(),
+        }
+    }
+
+    pub fn line(&mut self, line: &str) -> String {
+        self.line_number += 1;
+        let mut line = line.replace('\r', "");
+
+        if self.line_number == 1 && self.normalization.strip_bom && line.starts_with(BOM) {
+            line.remove(0);
+            self.stripped_bom = true;
+        }
+
+        if let Some(tab_width) = self.normalization.tab_width {
+            if line.contains('\t') {
+                line = expand_tabs(&line, tab_width);
+                self.expanded_lines.push(self.line_number);
+            }
+        }
+
+        if self.normalization.trim_trailing_whitespace {
+            let trimmed_len = line.trim_end().len();
+            if trimmed_len < line.len() {
+                line.truncate(trimmed_len);
+                self.trimmed_lines.push(self.line_number);
+            }
+        }
+
+        line
+    }
+
+    pub fn line_count(&self) -> usize {
+        self.line_number
+    }
+
+    // Describes each step that changed at least one line so far.
+    pub fn applied(&self) -> Vec<String> {
+        let mut applied: Vec<String> = Vec::new();
+        if self.stripped_bom {
+            applied.push(String::from("stripped UTF-8 BOM"));
+        }
+        applied.extend(describe_lines("expanded tabs", &self.expanded_lines));
+        applied.extend(describe_lines("trimmed trailing whitespace", &self.trimmed_lines));
+        applied
+    }
+}
+
+// Returns the normalized lines and a description of each step that changed the input.
+pub fn normalize(content: &str, normalization: &Normalization) -> (Vec<String>, Vec<String>) {
+    let mut normalizer = LineNormalizer::new(normalization);
+    let mut content = content.replace('\r', "");
+    let mut added_newline = false;
+
+    if normalization.ensure_final_newline {
+        let text = content.strip_prefix(BOM).unwrap_or(&content);
+        if !text.is_empty() && !text.ends_with('\n') {
+            content.push('\n');
+            added_newline = true;
+        }
+    }
+
+    let lines: Vec<String> = content.split('\n').map(|line| normalizer.line(line)).collect();
+
+    let mut applied = normalizer.applied();
+    if added_newline {
+        applied.push(String::from("added missing final newline"));
+    }
+
+    (lines, applied)
+}
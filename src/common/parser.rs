@@ -0,0 +1,232 @@
+use std::str::FromStr;
+
+use crate::common::Error;
+
+// A small set of parser combinators. A parser is any
+// `Fn(Span) -> PResult<T>`; on success it returns the value and the
+// remaining input, on failure an Error::Parse carrying the byte offset.
+
+#[derive(Debug, Clone, Copy)]
+pub struct Span<'a> {
+    source: &'a str,
+    pos: usize,
+}
+
+impl<'a> Span<'a> {
+    pub fn new(source: &'a str) -> Span<'a> {
+        Span { source, pos: 0 }
+    }
+
+    pub fn rest(&self) -> &'a str {
+        &self.source[self.pos..]
+    }
+
+    pub fn pos(&self) -> usize {
+        self.pos
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pos >= self.source.len()
+    }
+
+    fn advance(&self, n: usize) -> Span<'a> {
+        Span { source: self.source, pos: self.pos + n }
+    }
+
+    pub fn error<T>(&self, message: String) -> PResult<'a, T> {
+        Err(Error::Parse { pos: self.pos, message })
+    }
+}
+
+pub type PResult<'a, T> = Result<(T, Span<'a>), Error>;
+
+fn error_pos(e: &Error) -> usize {
+    match e {
+        Error::Parse { pos, .. } => *pos,
+        _ => 0
+    }
+}
+
+// Runs parser over the whole of line; trailing input is an error.
+pub fn parse_all<'a, T>(parser: impl Fn(Span<'a>) -> PResult<'a, T>, line: &'a str) -> Result<T, Error> {
+    let result = parser(Span::new(line)).and_then(|(value, rest)| {
+        if rest.is_empty() {
+            Ok(value)
+        }
+        else {
+            Err(Error::Parse { pos: rest.pos(), message: format!("Unexpected input: [{}]", rest.rest()) })
+        }
+    });
+
+    result.map_err(|e| match e {
+        Error::Parse { pos, message } => Error::Parse { pos, message: format!("{} in line [{}]", message, line) },
+        e => e
+    })
+}
+
+pub fn tag<'a>(t: &'static str) -> impl Fn(Span<'a>) -> PResult<'a, &'a str> {
+    move |input: Span<'a>| {
+        if input.rest().starts_with(t) {
+            Ok((&input.rest()[..t.len()], input.advance(t.len())))
+        }
+        else {
+            input.error(format!("Expected [{}]", t))
+        }
+    }
+}
+
+fn take_while1<'a>(what: &'static str, pred: impl Fn(char) -> bool) -> impl Fn(Span<'a>) -> PResult<'a, &'a str> {
+    move |input: Span<'a>| {
+        let rest = input.rest();
+        let len = rest.find(|c| !pred(c)).unwrap_or(rest.len());
+        if len == 0 {
+            input.error(format!("Expected {}", what))
+        }
+        else {
+            Ok((&rest[..len], input.advance(len)))
+        }
+    }
+}
+
+// One or more spaces or tabs.
+pub fn whitespace<'a>() -> impl Fn(Span<'a>) -> PResult<'a, &'a str> {
+    take_while1("whitespace", |c| c == ' ' || c == '\t')
+}
+
+// One or more non-whitespace characters.
+pub fn token<'a>() -> impl Fn(Span<'a>) -> PResult<'a, &'a str> {
+    take_while1("token", |c| !c.is_whitespace())
+}
+
+// An optionally negative decimal number, converted with FromStr.
+pub fn integer<'a, T: FromStr>() -> impl Fn(Span<'a>) -> PResult<'a, T> {
+    move |input: Span<'a>| {
+        let rest = input.rest();
+        let sign_len = if rest.starts_with('-') { 1 } else { 0 };
+        let digits_len = rest[sign_len..].find(|c: char| !c.is_ascii_digit()).unwrap_or(rest.len() - sign_len);
+
+        if digits_len == 0 {
+            return input.error(String::from("Expected integer"));
+        }
+
+        let len = sign_len + digits_len;
+        match rest[..len].parse::<T>() {
+            Ok(n) => Ok((n, input.advance(len))),
+            Err(_) => input.error(format!("Integer out of range: {}", &rest[..len]))
+        }
+    }
+}
+
+pub fn map<'a, T, U>(
+    parser: impl Fn(Span<'a>) -> PResult<'a, T>,
+    f: impl Fn(T) -> U)
+    -> impl Fn(Span<'a>) -> PResult<'a, U>
+{
+    move |input: Span<'a>| parser(input).map(|(value, rest)| (f(value), rest))
+}
+
+pub fn pair<'a, T, U>(
+    first: impl Fn(Span<'a>) -> PResult<'a, T>,
+    second: impl Fn(Span<'a>) -> PResult<'a, U>)
+    -> impl Fn(Span<'a>) -> PResult<'a, (T, U)>
+{
+    move |input: Span<'a>| {
+        let (a, input) = first(input)?;
+        let (b, input) = second(input)?;
+        Ok(((a, b), input))
+    }
+}
+
+pub fn preceded<'a, T, U>(
+    prefix: impl Fn(Span<'a>) -> PResult<'a, T>,
+    parser: impl Fn(Span<'a>) -> PResult<'a, U>)
+    -> impl Fn(Span<'a>) -> PResult<'a, U>
+{
+    map(pair(prefix, parser), |(_, value)| value)
+}
+
+pub fn terminated<'a, T, U>(
+    parser: impl Fn(Span<'a>) -> PResult<'a, T>,
+    suffix: impl Fn(Span<'a>) -> PResult<'a, U>)
+    -> impl Fn(Span<'a>) -> PResult<'a, T>
+{
+    map(pair(parser, suffix), |(value, _)| value)
+}
+
+pub fn opt<'a, T>(parser: impl Fn(Span<'a>) -> PResult<'a, T>) -> impl Fn(Span<'a>) -> PResult<'a, Option<T>> {
+    move |input: Span<'a>| match parser(input) {
+        Ok((value, rest)) => Ok((Some(value), rest)),
+        Err(_) => Ok((None, input))
+    }
+}
+
+// Zero or more repetitions. Stops when the parser fails or consumes nothing.
+#[allow(dead_code)]
+pub fn many<'a, T>(parser: impl Fn(Span<'a>) -> PResult<'a, T>) -> impl Fn(Span<'a>) -> PResult<'a, Vec<T>> {
+    move |mut input: Span<'a>| {
+        let mut values: Vec<T> = Vec::new();
+        while let Ok((value, rest)) = parser(input) {
+            if rest.pos() == input.pos() {
+                break;
+            }
+            values.push(value);
+            input = rest;
+        }
+        Ok((values, input))
+    }
+}
+
+// One or more items separated by sep.
+pub fn separated_list<'a, T, S>(
+    item: impl Fn(Span<'a>) -> PResult<'a, T>,
+    sep: impl Fn(Span<'a>) -> PResult<'a, S>)
+    -> impl Fn(Span<'a>) -> PResult<'a, Vec<T>>
+{
+    move |input: Span<'a>| {
+        let (first, mut input) = item(input)?;
+        let mut values: Vec<T> = vec![first];
+
+        while let Ok((_, after_sep)) = sep(input) {
+            let (value, rest) = item(after_sep)?;
+            values.push(value);
+            input = rest;
+        }
+
+        Ok((values, input))
+    }
+}
+
+pub trait Alt<'a, T> {
+    fn choice(&self, input: Span<'a>) -> PResult<'a, T>;
+}
+
+macro_rules! impl_alt {
+    ($($p:ident : $idx:tt),*) => {
+        impl<'a, T, $($p: Fn(Span<'a>) -> PResult<'a, T>),*> Alt<'a, T> for ($($p,)*) {
+            fn choice(&self, input: Span<'a>) -> PResult<'a, T> {
+                let mut furthest: Option<Error> = None;
+                $(
+                    match (self.$idx)(input) {
+                        Ok(result) => return Ok(result),
+                        Err(e) => {
+                            if furthest.as_ref().is_none_or(|f| error_pos(&e) > error_pos(f)) {
+                                furthest = Some(e);
+                            }
+                        }
+                    }
+                )*
+                Err(furthest.unwrap())
+            }
+        }
+    };
+}
+
+impl_alt!(A: 0, B: 1);
+impl_alt!(A: 0, B: 1, C: 2);
+impl_alt!(A: 0, B: 1, C: 2, D: 3);
+impl_alt!(A: 0, B: 1, C: 2, D: 3, E: 4);
+
+// Tries each parser of a tuple in order; if all fail, reports the error that got furthest.
+pub fn alt<'a, T>(parsers: impl Alt<'a, T>) -> impl Fn(Span<'a>) -> PResult<'a, T> {
+    move |input: Span<'a>| parsers.choice(input)
+}
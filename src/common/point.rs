@@ -0,0 +1,147 @@
+use std::ops::{Add, AddAssign, Mul, Neg, Sub, SubAssign};
+use std::str::FromStr;
+
+use crate::common::Error;
+
+// Screen coordinates: x grows to the right and y grows downwards,
+// matching how Grid lays out its rows.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Default)]
+pub struct Point {
+    pub x: i32,
+    pub y: i32,
+}
+
+// Offsets and positions share a representation; the alias documents intent.
+pub type Vec2 = Point;
+
+impl Point {
+    pub const ORIGIN: Point = Point { x: 0, y: 0 };
+
+    pub const fn new(x: i32, y: i32) -> Point {
+        Point { x, y }
+    }
+
+    #[allow(dead_code)]
+    pub fn manhattan(self, other: Point) -> i32 {
+        (self.x - other.x).abs() + (self.y - other.y).abs()
+    }
+
+    pub fn chebyshev(self, other: Point) -> i32 {
+        (self.x - other.x).abs().max((self.y - other.y).abs())
+    }
+
+    pub fn signum(self) -> Vec2 {
+        Point::new(self.x.signum(), self.y.signum())
+    }
+}
+
+impl From<(i32, i32)> for Point {
+    fn from(t: (i32, i32)) -> Point {
+        Point::new(t.0, t.1)
+    }
+}
+
+impl Add for Point {
+    type Output = Point;
+
+    fn add(self, other: Point) -> Point {
+        Point::new(self.x + other.x, self.y + other.y)
+    }
+}
+
+impl AddAssign for Point {
+    fn add_assign(&mut self, other: Point) {
+        *self = *self + other;
+    }
+}
+
+impl Sub for Point {
+    type Output = Point;
+
+    fn sub(self, other: Point) -> Point {
+        Point::new(self.x - other.x, self.y - other.y)
+    }
+}
+
+impl SubAssign for Point {
+    fn sub_assign(&mut self, other: Point) {
+        *self = *self - other;
+    }
+}
+
+impl Neg for Point {
+    type Output = Point;
+
+    fn neg(self) -> Point {
+        Point::new(-self.x, -self.y)
+    }
+}
+
+impl Mul<i32> for Point {
+    type Output = Point;
+
+    fn mul(self, n: i32) -> Point {
+        Point::new(self.x * n, self.y * n)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Direction {
+    Up,
+    Right,
+    Down,
+    Left
+}
+
+impl Direction {
+    // Clockwise, starting from Up.
+    pub const ALL: [Direction; 4] = [Direction::Up, Direction::Right, Direction::Down, Direction::Left];
+
+    // Accepts U/D/L/R as well as compass points N/E/S/W.
+    pub fn from(s: &str) -> Result<Direction, Error> {
+        match s {
+            "U" | "N" => Ok(Direction::Up),
+            "D" | "S" => Ok(Direction::Down),
+            "L" | "W" => Ok(Direction::Left),
+            "R" | "E" => Ok(Direction::Right),
+            _ => Err(Error::General(format!("Invalid direction: [{}]", s)))
+        }
+    }
+
+    pub fn get_movement_vector(self) -> Vec2 {
+        match self {
+            Direction::Up => Point::new(0, -1),
+            Direction::Right => Point::new(1, 0),
+            Direction::Down => Point::new(0, 1),
+            Direction::Left => Point::new(-1, 0),
+        }
+    }
+
+    #[allow(dead_code)]
+    pub fn turn_right(self) -> Direction {
+        match self {
+            Direction::Up => Direction::Right,
+            Direction::Right => Direction::Down,
+            Direction::Down => Direction::Left,
+            Direction::Left => Direction::Up,
+        }
+    }
+
+    #[allow(dead_code)]
+    pub fn turn_left(self) -> Direction {
+        self.turn_right().turn_right().turn_right()
+    }
+
+    #[allow(dead_code)]
+    pub fn reverse(self) -> Direction {
+        self.turn_right().turn_right()
+    }
+}
+
+impl FromStr for Direction {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Direction, Error> {
+        Direction::from(s)
+    }
+}
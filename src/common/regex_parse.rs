@@ -0,0 +1,69 @@
+use std::fmt::Debug;
+use std::str::FromStr;
+
+use regex::Captures;
+
+use crate::common::Error;
+
+// Converts capture group idx of a matched line into T. Used by the macros below.
+pub fn capture<T>(c: &Captures, idx: usize, name: &str, line: &str) -> Result<T, Error>
+    where T: FromStr, T::Err: Debug
+{
+    match c.get(idx) {
+        None => Err(Error::General(format!("Missing capture {} for {} in line: [{}]", idx, name, line))),
+        Some(m) => m
+            .as_str()
+            .parse::<T>()
+            .map_err(|e| Error::General(format!("Invalid value for {} in line [{}]: {:?}", name, line, e)))
+    }
+}
+
+// Declares a struct whose fields are filled, in order, from the capture groups
+// of a regex. Generates `fn from(line: &str) -> Result<Self, Error>`.
+//
+//  regex_struct! {
+//      #[derive(Debug)]
+//      struct MoveOperation = r"^move (\d+) from (\d+) to (\d+)$" {
+//          n: u32,
+//          from: usize,
+//          to: usize,
+//      }
+//  }
+macro_rules! regex_struct {
+    (
+        $(#[$meta:meta])*
+        $vis:vis struct $name:ident = $re:literal {
+            $($field_vis:vis $field:ident : $ty:ty),* $(,)?
+        }
+    ) => {
+        $(#[$meta])*
+        $vis struct $name {
+            $($field_vis $field: $ty),*
+        }
+
+        impl $name {
+            #[allow(unused_assignments)]
+            fn from(line: &str) -> Result<$name, $crate::common::Error> {
+                lazy_static::lazy_static! {
+                    static ref LINE_RE: regex::Regex = regex::Regex::new($re).unwrap();
+                }
+
+                match LINE_RE.captures(line) {
+                    None => Err($crate::common::Error::General(
+                        format!("Invalid {} line: [{}]", stringify!($name), line))),
+                    Some(c) => {
+                        let mut idx = 0;
+                        Ok($name {
+                            $($field: {
+                                idx += 1;
+                                $crate::common::regex_parse::capture::<$ty>(&c, idx, stringify!($field), line)?
+                            }),*
+                        })
+                    }
+                }
+            }
+        }
+    };
+}
+
+pub(crate) use regex_struct;
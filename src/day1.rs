@@ -1,64 +1,272 @@
-use crate::common::{read_lines, Error};
+use std::cmp::{Ordering, Reverse};
+use std::collections::BinaryHeap;
+use crate::common::{self, Error, ToolArgs, log};
 
-struct Elve {
-    calorie_total: i32
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Elve {
+    // Position of the elf in the input, starting at 0.
+    index: usize,
+    calorie_total: u64
 }
 
-fn parse_calorie_lists(path: &str) -> Result<Vec<Vec<i32>>, Error> {
-    let mut calories: Vec<i32> = Vec::new();
-    let mut elve_items: Vec<Vec<i32>> = Vec::new();
+impl Elve {
+    fn new(index: usize, items: &[u64]) -> Result<Elve, Error> {
+        let calorie_total = checked_total(index, items)?;
+        Ok(Elve { index, calorie_total })
+    }
+
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    pub fn calorie_total(&self) -> u64 {
+        self.calorie_total
+    }
+}
+
+// Orders by total; on a tie the earlier elf ranks higher.
+impl Ord for Elve {
+    fn cmp(&self, other: &Elve) -> Ordering {
+        self.calorie_total
+            .cmp(&other.calorie_total)
+            .then_with(|| other.index.cmp(&self.index))
+    }
+}
+
+impl PartialOrd for Elve {
+    fn partial_cmp(&self, other: &Elve) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+// Streams the blank-line separated calorie groups of some lines, one elf at a time.
+struct CalorieGroups<I: Iterator<Item = Result<String, Error>>> {
+    lines: I,
+    line_number: usize,
+}
+
+impl<I: Iterator<Item = Result<String, Error>>> CalorieGroups<I> {
+    fn new(lines: I) -> CalorieGroups<I> {
+        CalorieGroups { lines, line_number: 0 }
+    }
+}
+
+impl<I: Iterator<Item = Result<String, Error>>> Iterator for CalorieGroups<I> {
+    type Item = Result<Vec<u64>, Error>;
+
+    fn next(&mut self) -> Option<Result<Vec<u64>, Error>> {
+        let mut calories: Vec<u64> = Vec::new();
 
-    for line in read_lines(path)? {
-        if line == "" {
-            if calories.len() > 0 {
-                elve_items.push(calories);
-                calories = Vec::new();
+        for line in self.lines.by_ref() {
+            self.line_number += 1;
+
+            let line = match line {
+                Err(e) => return Some(Err(e)),
+                Ok(line) => line
+            };
+
+            if line.is_empty() {
+                if !calories.is_empty() {
+                    return Some(Ok(calories));
+                }
+            }
+            else {
+                match line.parse::<u64>() {
+                    Err(e) => return Some(Err(Error::General(format!("Invalid line {}: {}", self.line_number, e)))),
+                    Ok(calorie) => calories.push(calorie)
+                }
             }
         }
+
+        if calories.is_empty() {
+            None
+        }
         else {
-            match line.parse::<i32>() {
-                Err(e) => return Err(Error::General(format!("Invalid line: {}", e))),
-                Ok(calorie) => calories.push(calorie)
-            }
+            Some(Ok(calories))
         }
     }
+}
+
+fn checked_total(index: usize, items: &[u64]) -> Result<u64, Error> {
+    items
+        .iter()
+        .try_fold(0u64, |total, c| total.checked_add(*c))
+        .ok_or_else(|| Error::General(format!("Calorie total overflows for elf {}", index)))
+}
+
+// Returns the k elves carrying the most calories, largest first. Only k elves
+// are held at a time, so memory doesn't grow with the input. Ties keep the
+// earlier elf.
+pub fn top_k_elves<I: Iterator<Item = Result<String, Error>>>(lines: I, k: usize) -> Result<Vec<Elve>, Error> {
+    let mut heap: BinaryHeap<Reverse<Elve>> = BinaryHeap::with_capacity(k + 1);
 
-    if calories.len() > 0 {
-        elve_items.push(calories);
+    for (index, items) in CalorieGroups::new(lines).enumerate() {
+        heap.push(Reverse(Elve::new(index, &items?)?));
+        if heap.len() > k {
+            heap.pop();
+        }
     }
 
-    Ok(elve_items)
+    Ok(heap
+        .into_sorted_vec()
+        .into_iter()
+        .map(|Reverse(elve)| elve)
+        .collect())
 }
 
-fn to_elves(elve_items: Vec<Vec<i32>>) -> Vec<Elve> {
-    elve_items
+fn run_part(path: &str, k: usize) -> Result<String, Error> {
+    let elves = top_k_elves(common::stream_lines(path)?, k)?;
+
+    if elves.len() < k {
+        return Err(Error::General(format!("Expected at least {} elves, found {}", k, elves.len())));
+    }
+
+    let top: Vec<String> = elves.iter().map(|e| format!("elf {} ({})", e.index(), e.calorie_total())).collect();
+    log::debug!("Top {} elves: {}", k, top.join(", "));
+
+    let total = elves
         .iter()
-        .map(|items| Elve { calorie_total: items.iter().sum() })
-        .collect()
+        .try_fold(0u64, |total, e| total.checked_add(e.calorie_total()))
+        .ok_or_else(|| Error::General(String::from("Calorie total overflows")))?;
+
+    Ok(total.to_string())
 }
 
 pub fn part1(path: &str) -> Result<String, Error> {
-    let elve_items = parse_calorie_lists(path)?;
-    let elves = to_elves(elve_items);
+    run_part(path, 1)
+}
 
-    let elve = elves
-        .iter()
-        .max_by_key(|e| e.calorie_total)
-        .expect("There was less than one.");
+pub fn part2(path: &str) -> Result<String, Error> {
+    run_part(path, 3)
+}
 
-    Ok(elve.calorie_total.to_string())
+struct Summary {
+    count: usize,
+    mean: f64,
+    median: f64,
+    std_dev: f64,
 }
 
-pub fn part2(path: &str) -> Result<String, Error> {
-    let elve_items = parse_calorie_lists(path)?;
-    let mut elves = to_elves(elve_items);
+impl Summary {
+    fn of(values: &[u64]) -> Summary {
+        let count = values.len();
+        if count == 0 {
+            return Summary { count, mean: 0.0, median: 0.0, std_dev: 0.0 };
+        }
 
-    elves.sort_by_key(|e| e.calorie_total * -1);
-    let top3_sum: i32 = elves
-        .iter()
-        .take(3)
-        .map(|e| e.calorie_total)
-        .sum();
+        let mean = values.iter().map(|v| *v as f64).sum::<f64>() / count as f64;
+        let variance = values.iter().map(|v| (*v as f64 - mean).powi(2)).sum::<f64>() / count as f64;
 
-    Ok(top3_sum.to_string())
+        Summary { count, mean, median: quantile(values, 0.5), std_dev: variance.sqrt() }
+    }
 }
+
+// Linear interpolation between the closest ranks.
+fn quantile(values: &[u64], q: f64) -> f64 {
+    let mut sorted = values.to_vec();
+    sorted.sort_unstable();
+
+    let rank = q * (sorted.len() - 1) as f64;
+    let lower = rank.floor() as usize;
+    let upper = rank.ceil() as usize;
+    let frac = rank - lower as f64;
+
+    sorted[lower] as f64 + (sorted[upper] as f64 - sorted[lower] as f64) * frac
+}
+
+// Tukey's fences: values further than 1.5 IQR outside the quartiles.
+fn outlier_fences(values: &[u64]) -> (f64, f64) {
+    let q1 = quantile(values, 0.25);
+    let q3 = quantile(values, 0.75);
+    let iqr = q3 - q1;
+    (q1 - 1.5 * iqr, q3 + 1.5 * iqr)
+}
+
+fn histogram(values: &[u64], buckets: usize, bar_width: usize) -> String {
+    let min = *values.iter().min().unwrap();
+    let max = *values.iter().max().unwrap();
+    let bucket_size = ((max - min) / buckets as u64 + 1).max(1);
+
+    let mut counts: Vec<usize> = vec![0; buckets];
+    for v in values {
+        counts[((v - min) / bucket_size) as usize] += 1;
+    }
+
+    let max_count = *counts.iter().max().unwrap();
+    let mut content = String::new();
+
+    for (idx, count) in counts.iter().enumerate() {
+        let from = min + idx as u64 * bucket_size;
+        let bar_len = (count * bar_width).div_ceil(max_count);
+        content.push_str(&format!(
+            "{:>8} - {:<8} | {:<width$} {}\n",
+            from, from + bucket_size - 1, "#".repeat(bar_len), count, width = bar_width));
+    }
+
+    content
+}
+
+// An elf with everything it carries, only kept around by stats.
+struct Inventory {
+    elve: Elve,
+    items: Vec<u64>,
+}
+
+// Reports per-elf item statistics, a histogram of totals and outliers.
+// Args: [input_path]
+pub fn stats(args: &[String]) -> Result<String, Error> {
+    let args = ToolArgs::parse(args, &[], &[])?;
+    let path = args.positional_or(0, "puzzles/day1-input.txt");
+
+    let inventories = CalorieGroups::new(common::stream_lines(path)?)
+        .enumerate()
+        .map(|(index, items)| {
+            let items = items?;
+            Ok(Inventory { elve: Elve::new(index, &items)?, items })
+        })
+        .collect::<Result<Vec<Inventory>, Error>>()?;
+
+    if inventories.is_empty() {
+        return Err(Error::General(format!("No elves found in {}", path)));
+    }
+
+    let totals: Vec<u64> = inventories.iter().map(|i| i.elve.calorie_total).collect();
+    let (low_fence, high_fence) = outlier_fences(&totals);
+    let is_outlier = |total: u64| (total as f64) < low_fence || (total as f64) > high_fence;
+
+    let mut content = String::new();
+    content.push_str(&format!(
+        "{:>5} {:>5} {:>8} {:>10} {:>10} {:>10}  {}\n",
+        "Elf", "Items", "Total", "Mean", "Median", "StdDev", "Outlier"));
+
+    for inventory in &inventories {
+        let elve = inventory.elve;
+        let summary = Summary::of(&inventory.items);
+        let row = format!(
+            "{:>5} {:>5} {:>8} {:>10.2} {:>10.2} {:>10.2}  {}",
+            elve.index, summary.count, elve.calorie_total, summary.mean, summary.median, summary.std_dev,
+            if is_outlier(elve.calorie_total) { "*" } else { "" });
+        content.push_str(row.trim_end());
+        content.push('\n');
+    }
+
+    let summary = Summary::of(&totals);
+    content.push_str(&format!(
+        "\nElves: {}, items: {}, totals mean {:.2}, median {:.2}, std dev {:.2}\n",
+        summary.count, inventories.iter().map(|i| i.items.len()).sum::<usize>(), summary.mean, summary.median, summary.std_dev));
+
+    content.push_str("\nHistogram of totals:\n");
+    content.push_str(&histogram(&totals, 10, 50));
+
+    let outliers: Vec<String> = inventories
+        .iter()
+        .map(|i| i.elve)
+        .filter(|e| is_outlier(e.calorie_total))
+        .map(|e| format!("elf {} ({})", e.index, e.calorie_total))
+        .collect();
+
+    content.push_str(&format!("\nOutliers outside [{:.2}, {:.2}]: {}\n",
+        low_fence, high_fence, if outliers.is_empty() { String::from("none") } else { outliers.join(", ") }));
+
+    Ok(content)
+}
\ No newline at end of file
@@ -1,6 +1,5 @@
 use crate::common::{Error, read_non_empty_lines};
-use regex::Regex;
-use lazy_static::lazy_static;
+use crate::common::parser::{alt, integer, map, opt, pair, parse_all, preceded, tag, terminated, whitespace};
 
 #[derive(Debug)]
 enum Command {
@@ -10,24 +9,10 @@ enum Command {
 
 impl Command {
     fn from(line: &str) -> Result<Command, Error> {
-        lazy_static! { static ref REGEX: Regex = Regex::new(r"(addx)\s+(-?\d+)|(noop)").unwrap(); }
-
-        match REGEX.captures(line) {
-            None => {
-                Err(Error::General(format!("xxx Invalid line: [{}]", line)))
-            },
-            Some(c) => {
-                if c.get(1).is_some() {
-                    Ok(Command::Addx(c.get(2).unwrap().as_str().parse::<i32>().unwrap()))
-                }
-                else if c.get(3).is_some() {
-                    Ok(Command::Noop)
-                }
-                else {
-                    Err(Error::General(format!("Invalid line: [{}]", line)))
-                }
-            }
-        }
+        parse_all(terminated(alt((
+            map(tag("noop"), |_| Command::Noop),
+            map(preceded(pair(tag("addx"), whitespace()), integer()), Command::Addx),
+        )), opt(whitespace())), line)
     }
 
     fn get_cycle_count(self: &Command) -> i32 {
@@ -80,50 +65,33 @@ impl ProgramState {
 }
 
 mod pixel {
+    use crate::common::{Grid, Point};
     use crate::day10::ProgramState;
-    use std::collections::HashSet;
 
-    pub fn from_cycle(cycle: i32) -> (i32, i32) {
-        let x = (cycle - 1) % 40;
-        let y = (cycle - 1) / 40;
-        (x, y)
+    const SCREEN_WIDTH: usize = 40;
+    const SCREEN_HEIGHT: usize = 6;
+
+    pub fn from_cycle(cycle: i32) -> Point {
+        Point::new((cycle - 1) % SCREEN_WIDTH as i32, (cycle - 1) / SCREEN_WIDTH as i32)
     }
 
-    pub fn get_pixels(program_states: &Vec<(ProgramState, ProgramState)>) -> HashSet<(i32, i32)> {
-        let mut pixels: HashSet<(i32, i32)> = HashSet::new();
+    pub fn get_pixels(program_states: &Vec<(ProgramState, ProgramState)>) -> Grid<char> {
+        let mut pixels: Grid<char> = Grid::new(SCREEN_WIDTH, SCREEN_HEIGHT, '.');
     
         for (st, next_st) in program_states {
             for cycle in st.cycle..next_st.cycle {
-                let (x, y) = from_cycle(cycle);
-                for delta in -1..2 {
-                    let sprite_x = st.x + delta;
-                    if sprite_x == x {
-                        pixels.insert((x, y));
-                    }
+                let pos = from_cycle(cycle);
+                if (st.x - pos.x).abs() <= 1 {
+                    pixels.set(pos, '#');
                 }
-    
             }
         }
     
         pixels
     }
 
-    pub fn render(pixels: &HashSet<(i32, i32)>) -> String {
-        let mut content: String = String::new();
-
-        for y in 0..6 {
-            for x in 0..40 {
-                if pixels.contains(&(x, y)) {
-                    content.push('#');
-                }
-                else {
-                    content.push('.');
-                }
-            }
-            content.push('\n');
-        }
-
-        content
+    pub fn render(pixels: &Grid<char>) -> String {
+        pixels.to_string()
     }
 }
 
@@ -1,131 +1,522 @@
-use crate::common::{Error, read_non_empty_lines};
+use std::collections::HashMap;
+use std::fs::read_to_string;
+
+use crate::common::{Error, ToolArgs, read_non_empty_lines};
 
 use regex::Regex;
 use lazy_static::lazy_static;
 
-#[derive(Debug, Clone, Copy)]
-enum GameChoice {
-    Rock,
-    Paper,
-    Scissor
-}
+// Index into Game::choices.
+type GameChoice = usize;
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 enum GameResult {
     Loose,
     Draw,
     Win
 }
 
-fn get_game_result(c: char) -> Result<GameResult, Error> {
-    match c {
-        'X' => Ok(GameResult::Loose),
-        'Y' => Ok(GameResult::Draw),
-        'Z' => Ok(GameResult::Win),
-        _ => Err(Error::General(format!("Invalid character for game result: {}", c)))
+impl GameResult {
+    fn from(s: &str) -> Result<GameResult, Error> {
+        match s.to_lowercase().as_str() {
+            "loss" | "lose" | "loose" => Ok(GameResult::Loose),
+            "draw" => Ok(GameResult::Draw),
+            "win" => Ok(GameResult::Win),
+            _ => Err(Error::General(format!("Invalid game result: {}", s)))
+        }
     }
-}
 
-fn get_game_choice(c: char) -> Result<GameChoice, Error> {
-    match c {
-        'A' => Ok(GameChoice::Rock),
-        'B' => Ok(GameChoice::Paper),
-        'C' => Ok(GameChoice::Scissor),
-        'X' => Ok(GameChoice::Rock),
-        'Y' => Ok(GameChoice::Paper),
-        'Z' => Ok(GameChoice::Scissor),
-        _ => Err(Error::General(format!("Invalid character for game choice: {}", c)))
+    fn label(self) -> &'static str {
+        match self {
+            GameResult::Loose => "loss",
+            GameResult::Draw => "draw",
+            GameResult::Win => "win"
+        }
     }
 }
 
-fn parse_line_char_codes(line: String) -> Result<(char, char), Error> {
-    lazy_static! {
-        static ref LINE_RE: Regex = Regex::new(r"^([ABC])\s+([XYZ])").unwrap();
-    }
+// What the second column of the strategy guide means.
+#[derive(Debug, Clone)]
+enum PlayerLetters {
+    Choice(HashMap<char, GameChoice>),
+    Outcome(HashMap<char, GameResult>),
+}
 
-    match LINE_RE.captures(line.as_str()) {
-        Some(c) => {
-            let c1 = c.get(1).unwrap().as_str().chars().next().unwrap();
-            let c2 = c.get(2).unwrap().as_str().chars().next().unwrap();
-            return Ok((c1, c2));
+// A cyclic tournament game described by a config file:
+//
+//  # comments start with '#'
+//  choices: Rock Paper Scissors
+//  beats: cyclic                # or explicit pairs: Paper>Rock Scissors>Paper Rock>Scissors
+//  shape_scores: 1 2 3
+//  outcome_scores: 0 3 6        # loss draw win
+//  opponent: A=Rock B=Paper C=Scissors
+//  player: X=Rock Y=Paper Z=Scissors       # or player_outcome: X=loss Y=draw Z=win
+//
+// With "beats: cyclic" each choice beats the (n - 1) / 2 choices listed before
+// it (wrapping around), which needs an odd number of choices. Explicit pairs
+// must still leave every choice with something it beats and something that
+// beats it.
+#[derive(Debug, Clone)]
+pub struct Game {
+    name: String,
+    choices: Vec<String>,
+    // beats[a][b] is true when choice a beats choice b.
+    beats: Vec<Vec<bool>>,
+    shape_scores: Vec<i32>,
+    // Indexed by Loose, Draw, Win.
+    outcome_scores: [i32; 3],
+    opponent_letters: HashMap<char, GameChoice>,
+    player_letters: PlayerLetters,
+}
+
+const RPS_PART1_CONFIG: &str = "
+name: Rock Paper Scissors (part 1)
+choices: Rock Paper Scissors
+beats: cyclic
+shape_scores: 1 2 3
+outcome_scores: 0 3 6
+opponent: A=Rock B=Paper C=Scissors
+player: X=Rock Y=Paper Z=Scissors
+";
+
+const RPS_PART2_CONFIG: &str = "
+name: Rock Paper Scissors (part 2)
+choices: Rock Paper Scissors
+beats: cyclic
+shape_scores: 1 2 3
+outcome_scores: 0 3 6
+opponent: A=Rock B=Paper C=Scissors
+player_outcome: X=loss Y=draw Z=win
+";
+
+fn parse_letter_mapping<T>(value: &str, parse_target: impl Fn(&str) -> Result<T, Error>) -> Result<HashMap<char, T>, Error> {
+    let mut mapping: HashMap<char, T> = HashMap::new();
+
+    for entry in value.split_whitespace() {
+        let (letter, target) = entry
+            .split_once('=')
+            .ok_or_else(|| Error::General(format!("Invalid letter mapping: {}", entry)))?;
+
+        let mut letters = letter.chars();
+        match (letters.next(), letters.next()) {
+            (Some(c), None) => {
+                if mapping.insert(c, parse_target(target)?).is_some() {
+                    return Err(Error::General(format!("Letter mapped twice: {}", c)));
+                }
+            },
+            _ => return Err(Error::General(format!("Expected a single letter: {}", letter)))
         }
-        None => Err(Error::General(format!("Invalid input line: [{}]", line)))
     }
+
+    Ok(mapping)
 }
 
-fn parse_line_p1(line: String) -> Result<(GameChoice, GameChoice), Error> {
-    let (c1, c2) = parse_line_char_codes(line)?;
-    return Ok((get_game_choice(c1)?, get_game_choice(c2)?));
+fn parse_scores(value: &str) -> Result<Vec<i32>, Error> {
+    value
+        .split_whitespace()
+        .map(|s| s.parse::<i32>().map_err(|e| Error::General(format!("Invalid score {}: {}", s, e))))
+        .collect()
 }
 
-fn parse_line_p2(line: String) -> Result<(GameChoice, GameChoice), Error> {
-    let (c1, c2) = parse_line_char_codes(line)?;
-    let choice1 = get_game_choice(c1)?;
-    let result = get_game_result(c2)?;
+impl Game {
+    pub fn from_config(config: &str) -> Result<Game, Error> {
+        let mut settings: HashMap<&str, &str> = HashMap::new();
+
+        for line in config.lines() {
+            let line = line.split('#').next().unwrap_or("").trim();
+            if line.is_empty() {
+                continue;
+            }
+            match line.split_once(':') {
+                Some((key, value)) => {
+                    settings.insert(key.trim(), value.trim());
+                },
+                None => return Err(Error::General(format!("Invalid config line: [{}]", line)))
+            }
+        }
+
+        let setting = |key: &str| settings
+            .get(key)
+            .copied()
+            .ok_or_else(|| Error::General(format!("Missing config setting: {}", key)));
+
+        let name = String::from(settings.get("name").copied().unwrap_or("unnamed game"));
+        let choices: Vec<String> = setting("choices")?.split_whitespace().map(String::from).collect();
+        let n = choices.len();
+        if n < 2 {
+            return Err(Error::General(String::from("A game needs at least two choices")));
+        }
+
+        let choice_index = |s: &str| choices
+            .iter()
+            .position(|c| c == s)
+            .ok_or_else(|| Error::General(format!("Unknown choice: {}", s)));
+
+        let mut beats: Vec<Vec<bool>> = vec![vec![false; n]; n];
+        let beats_setting = setting("beats")?;
+        if beats_setting == "cyclic" {
+            if n.is_multiple_of(2) {
+                return Err(Error::General(format!("A cyclic game needs an odd number of choices, got {}", n)));
+            }
+            for (a, row) in beats.iter_mut().enumerate() {
+                for step in 1..=(n - 1) / 2 {
+                    row[(a + n - step) % n] = true;
+                }
+            }
+        }
+        else {
+            for pair in beats_setting.split_whitespace() {
+                let (winner, loser) = pair
+                    .split_once('>')
+                    .ok_or_else(|| Error::General(format!("Invalid beats pair: {}", pair)))?;
+                beats[choice_index(winner)?][choice_index(loser)?] = true;
+            }
+        }
+
+        for a in 0..n {
+            if beats[a][a] {
+                return Err(Error::General(format!("{} can't beat itself", choices[a])));
+            }
+            for b in (a + 1)..n {
+                if beats[a][b] == beats[b][a] {
+                    return Err(Error::General(format!(
+                        "Exactly one of {} and {} must beat the other", choices[a], choices[b])));
+                }
+            }
+        }
+
+        // Every result must be reachable against every choice, otherwise an
+        // outcome letter can ask for a win nothing delivers.
+        for a in 0..n {
+            if !beats[a].contains(&true) {
+                return Err(Error::General(format!("{} must beat at least one choice", choices[a])));
+            }
+            if !beats.iter().any(|row| row[a]) {
+                return Err(Error::General(format!("{} must be beaten by at least one choice", choices[a])));
+            }
+        }
 
-    Ok(match result {
-        GameResult::Draw => (choice1, choice1),
-        GameResult::Win => match choice1 {
-            GameChoice::Rock => (choice1, GameChoice::Paper),
-            GameChoice::Paper => (choice1, GameChoice::Scissor),
-            GameChoice::Scissor => (choice1, GameChoice::Rock)
-        },
-        GameResult::Loose => match choice1 {
-            GameChoice::Rock => (choice1, GameChoice::Scissor),
-            GameChoice::Paper => (choice1, GameChoice::Rock),
-            GameChoice::Scissor => (choice1, GameChoice::Paper)
+        let shape_scores = parse_scores(setting("shape_scores")?)?;
+        if shape_scores.len() != n {
+            return Err(Error::General(format!("Expected {} shape scores, got {}", n, shape_scores.len())));
         }
-    })
+
+        let outcome_scores: [i32; 3] = parse_scores(setting("outcome_scores")?)?
+            .try_into()
+            .map_err(|_| Error::General(String::from("Expected 3 outcome scores: loss draw win")))?;
+
+        let opponent_letters = parse_letter_mapping(setting("opponent")?, choice_index)?;
+
+        let player_letters = match (settings.get("player"), settings.get("player_outcome")) {
+            (Some(value), None) => PlayerLetters::Choice(parse_letter_mapping(value, choice_index)?),
+            (None, Some(value)) => PlayerLetters::Outcome(parse_letter_mapping(value, GameResult::from)?),
+            _ => return Err(Error::General(String::from("Expected exactly one of player or player_outcome")))
+        };
+
+        Ok(Game { name, choices, beats, shape_scores, outcome_scores, opponent_letters, player_letters })
+    }
+
+    pub fn from_file(path: &str) -> Result<Game, Error> {
+        Game::from_config(&read_to_string(path).map_err(Error::IOError)?)
+    }
+
+    fn get_game_result(&self, ours: GameChoice, theirs: GameChoice) -> GameResult {
+        if ours == theirs {
+            GameResult::Draw
+        }
+        else if self.beats[ours][theirs] {
+            GameResult::Win
+        }
+        else {
+            GameResult::Loose
+        }
+    }
+
+    // When several choices give the result, the highest scoring one is played.
+    // from_config guarantees every result is reachable against every choice.
+    fn choose_for_result(&self, theirs: GameChoice, result: GameResult) -> GameChoice {
+        (0..self.choices.len())
+            .filter(|ours| self.get_game_result(*ours, theirs) == result)
+            .rev()
+            .max_by_key(|ours| self.shape_scores[*ours])
+            .expect("every result is reachable in a validated game")
+    }
+
+    fn decode(&self, c1: char, c2: char) -> Result<(GameChoice, GameChoice), Error> {
+        let theirs = *self.opponent_letters
+            .get(&c1)
+            .ok_or_else(|| Error::General(format!("Invalid character for opponent: {}", c1)))?;
+
+        let ours = match &self.player_letters {
+            PlayerLetters::Choice(letters) => *letters
+                .get(&c2)
+                .ok_or_else(|| Error::General(format!("Invalid character for game choice: {}", c2)))?,
+            PlayerLetters::Outcome(letters) => {
+                let result = letters
+                    .get(&c2)
+                    .ok_or_else(|| Error::General(format!("Invalid character for game result: {}", c2)))?;
+                self.choose_for_result(theirs, *result)
+            }
+        };
+
+        Ok((theirs, ours))
+    }
 }
 
-type GameChoiceLineParser = fn(String) -> Result<(GameChoice, GameChoice), Error>;
+fn parse_line_char_codes(line: &str) -> Result<(char, char), Error> {
+    lazy_static! {
+        static ref LINE_RE: Regex = Regex::new(r"^(\S)\s+(\S)\s*$").unwrap();
+    }
+
+    match LINE_RE.captures(line) {
+        Some(c) => {
+            let c1 = c.get(1).unwrap().as_str().chars().next().unwrap();
+            let c2 = c.get(2).unwrap().as_str().chars().next().unwrap();
+            Ok((c1, c2))
+        }
+        None => Err(Error::General(format!("Invalid input line: [{}]", line)))
+    }
+}
 
-fn parse_input(path: &str, line_parser: GameChoiceLineParser) -> Result<Vec<(GameChoice, GameChoice)>, Error> {
+fn parse_input(path: &str, game: &Game) -> Result<Vec<(GameChoice, GameChoice)>, Error> {
     let lines = read_non_empty_lines(path)?;
     let mut all_choices: Vec<(GameChoice, GameChoice)> = Vec::new();
 
     for line in lines {
-        all_choices.push(line_parser(line)?)
+        let (c1, c2) = parse_line_char_codes(&line)?;
+        all_choices.push(game.decode(c1, c2)?)
     }
 
     Ok(all_choices)
 }
 
-fn calculate_score(c1: &GameChoice, c2: &GameChoice) -> i32 {
-    return match c1 {
-        GameChoice::Rock => match c2 {
-            GameChoice::Rock => 1 + 3,
-            GameChoice::Paper => 1 + 0,
-            GameChoice::Scissor => 1 + 6
-        },
-        GameChoice::Paper => match c2 {
-            GameChoice::Rock => 2 + 6,
-            GameChoice::Paper => 2 + 3,
-            GameChoice::Scissor => 2 + 0
-        },
-        GameChoice::Scissor => match c2 {
-            GameChoice::Rock => 3 + 0,
-            GameChoice::Paper => 3 + 6,
-            GameChoice::Scissor => 3 + 3
-        }
-    }
+fn calculate_score(game: &Game, ours: GameChoice, theirs: GameChoice) -> i32 {
+    let result = game.get_game_result(ours, theirs);
+    game.shape_scores[ours] + game.outcome_scores[result as usize]
 }
 
-fn run_part(path: &str, line_parser: GameChoiceLineParser) -> Result<String, Error> {
-    let all_choices = parse_input(path, line_parser)?;
+fn run_game(path: &str, game: &Game) -> Result<String, Error> {
+    let all_choices = parse_input(path, game)?;
 
     let score = all_choices
         .iter()
-        .map(|(c1, c2)| calculate_score(c2, c1))
+        .map(|(theirs, ours)| calculate_score(game, *ours, *theirs))
         .sum::<i32>();
 
     Ok(score.to_string())
 }
 
 pub fn part1(path: &str) -> Result<String, Error> {
-    run_part(path, parse_line_p1)
+    run_game(path, &Game::from_config(RPS_PART1_CONFIG)?)
 }
 
 pub fn part2(path: &str) -> Result<String, Error> {
-    run_part(path, parse_line_p2)
+    run_game(path, &Game::from_config(RPS_PART2_CONFIG)?)
+}
+
+fn load_game(name: &str) -> Result<Game, Error> {
+    match name {
+        "part1" => Game::from_config(RPS_PART1_CONFIG),
+        "part2" => Game::from_config(RPS_PART2_CONFIG),
+        config_path => Game::from_file(config_path)
+    }
+}
+
+fn csv_field(s: &str) -> String {
+    if s.contains([',', '"', '\n']) {
+        format!("\"{}\"", s.replace('"', "\"\""))
+    }
+    else {
+        String::from(s)
+    }
+}
+
+// One CSV row per round, so traces from two implementations can be diffed.
+fn explain_game(path: &str, game: &Game) -> Result<String, Error> {
+    let mut content = String::from("round,line,opponent,player,result,shape_score,outcome_score,round_score,running_total\n");
+    let mut running_total: i32 = 0;
+
+    for (idx, line) in read_non_empty_lines(path)?.iter().enumerate() {
+        let (c1, c2) = parse_line_char_codes(line)?;
+        let (theirs, ours) = game.decode(c1, c2)?;
+        let result = game.get_game_result(ours, theirs);
+        let shape_score = game.shape_scores[ours];
+        let outcome_score = game.outcome_scores[result as usize];
+        let round_score = calculate_score(game, ours, theirs);
+        running_total += round_score;
+
+        content.push_str(&format!("{},{},{},{},{},{},{},{},{}\n",
+            idx + 1, csv_field(line), csv_field(&game.choices[theirs]), csv_field(&game.choices[ours]),
+            result.label(), shape_score, outcome_score, round_score, running_total));
+    }
+
+    Ok(content)
+}
+
+// Scores a strategy guide under a game config file. With --explain, prints a
+// per-round CSV trace instead of the total.
+// Args: <config_path | part1 | part2> [input_path] [--explain]
+pub fn play(args: &[String]) -> Result<String, Error> {
+    let args = ToolArgs::parse(args, &["--explain"], &[])?;
+    let game = match args.positional(0) {
+        None => return Err(Error::General(String::from("Usage: 2 play <config_path | part1 | part2> [input_path] [--explain]"))),
+        Some(name) => load_game(name)?
+    };
+    let path = args.positional_or(1, "puzzles/day2-input.txt");
+
+    if args.flag("--explain") {
+        explain_game(path, &game)
+    }
+    else {
+        Ok(format!("{}: {}\n", game.name, run_game(path, &game)?))
+    }
+}
+
+// All orderings of k items chosen from items.
+fn permutations<T: Clone>(items: &[T], k: usize) -> Vec<Vec<T>> {
+    if k == 0 {
+        return vec![Vec::new()];
+    }
+
+    let mut result: Vec<Vec<T>> = Vec::new();
+    for idx in 0..items.len() {
+        let mut rest = items.to_vec();
+        let item = rest.remove(idx);
+        for mut tail in permutations(&rest, k - 1) {
+            tail.insert(0, item.clone());
+            result.push(tail);
+        }
+    }
+    result
+}
+
+fn describe_mapping<T>(letters: &[char], targets: &[T], describe: impl Fn(&T) -> String) -> String {
+    letters
+        .iter()
+        .zip(targets)
+        .map(|(letter, target)| format!("{}={}", letter, describe(target)))
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+type ScoredMapping<T> = (Vec<T>, i32);
+
+// Returns the lowest and highest scoring mappings, in that order.
+fn score_range<T>(
+    mappings: Vec<Vec<T>>,
+    score: impl Fn(&[T]) -> i32)
+    -> Option<(ScoredMapping<T>, ScoredMapping<T>)>
+    where T: Clone
+{
+    let scored: Vec<ScoredMapping<T>> = mappings
+        .into_iter()
+        .map(|m| { let s = score(&m); (m, s) })
+        .collect();
+
+    let worst = scored.iter().min_by_key(|(_, s)| *s)?.clone();
+    let best = scored.iter().max_by_key(|(_, s)| *s)?.clone();
+    Some((worst, best))
+}
+
+// Scores every way of reading the second column of the guide: as each
+// permutation of choices and as each permutation of outcomes. Also reports
+// the opponent's move distribution and the best fixed and adaptive responses.
+// Args: [config_path | part1 | part2] [input_path]
+pub fn analyze(args: &[String]) -> Result<String, Error> {
+    let args = ToolArgs::parse(args, &[], &[])?;
+    let game = load_game(args.positional_or(0, "part1"))?;
+    let path = args.positional_or(1, "puzzles/day2-input.txt");
+    let n = game.choices.len();
+
+    let mut letters: Vec<char> = Vec::new();
+    let mut rounds: Vec<(GameChoice, char)> = Vec::new();
+
+    for line in read_non_empty_lines(path)? {
+        let (c1, c2) = parse_line_char_codes(&line)?;
+        let theirs = *game.opponent_letters
+            .get(&c1)
+            .ok_or_else(|| Error::General(format!("Invalid character for opponent: {}", c1)))?;
+        if !letters.contains(&c2) {
+            letters.push(c2);
+        }
+        rounds.push((theirs, c2));
+    }
+    letters.sort_unstable();
+
+    // counts[theirs][letter index]
+    let mut counts: Vec<Vec<i32>> = vec![vec![0; letters.len()]; n];
+    for (theirs, c2) in &rounds {
+        counts[*theirs][letters.iter().position(|l| l == c2).unwrap()] += 1;
+    }
+    let score_with = |ours_for: &dyn Fn(GameChoice, usize) -> GameChoice| -> i32 {
+        (0..n)
+            .flat_map(|theirs| (0..letters.len()).map(move |l| (theirs, l)))
+            .map(|(theirs, l)| counts[theirs][l] * calculate_score(&game, ours_for(theirs, l), theirs))
+            .sum()
+    };
+
+    let mut content = format!("{}: {} rounds, player letters {}\n", game.name, rounds.len(), String::from_iter(&letters));
+
+    content.push_str("\nAs choices:\n");
+    if letters.len() > n {
+        content.push_str(&format!("  not possible, {} letters for {} choices\n", letters.len(), n));
+    }
+    else {
+        let choices: Vec<GameChoice> = (0..n).collect();
+        let range = score_range(permutations(&choices, letters.len()), |m| score_with(&|_, l| m[l]));
+        if let Some(((worst, worst_score), (best, best_score))) = range {
+            let describe = |c: &GameChoice| game.choices[*c].clone();
+            content.push_str(&format!("  best:  {:>8}  {}\n", best_score, describe_mapping(&letters, &best, describe)));
+            content.push_str(&format!("  worst: {:>8}  {}\n", worst_score, describe_mapping(&letters, &worst, describe)));
+        }
+    }
+
+    content.push_str("\nAs outcomes:\n");
+    if letters.len() != 3 {
+        content.push_str(&format!("  not possible, {} letters for 3 outcomes\n", letters.len()));
+    }
+    else {
+        let outcomes = vec![GameResult::Loose, GameResult::Draw, GameResult::Win];
+        let range = score_range(permutations(&outcomes, 3), |m| score_with(&|theirs, l| game.choose_for_result(theirs, m[l])));
+        if let Some(((worst, worst_score), (best, best_score))) = range {
+            let describe = |r: &GameResult| String::from(r.label());
+            content.push_str(&format!("  best:  {:>8}  {}\n", best_score, describe_mapping(&letters, &best, describe)));
+            content.push_str(&format!("  worst: {:>8}  {}\n", worst_score, describe_mapping(&letters, &worst, describe)));
+        }
+    }
+
+    content.push_str("\nOpponent moves:\n");
+    let mut opponent_counts: Vec<usize> = vec![0; n];
+    for (theirs, _) in &rounds {
+        opponent_counts[*theirs] += 1;
+    }
+    for (choice, count) in opponent_counts.iter().enumerate() {
+        content.push_str(&format!("  {:<10} {:>6} {:>6.1}%\n",
+            game.choices[choice], count, 100.0 * *count as f64 / rounds.len().max(1) as f64));
+    }
+
+    let score_against_all = |ours_for: &dyn Fn(GameChoice) -> GameChoice| -> i32 {
+        rounds.iter().map(|(theirs, _)| calculate_score(&game, ours_for(*theirs), *theirs)).sum()
+    };
+
+    content.push_str("\nFixed responses:\n");
+    let fixed_scores: Vec<i32> = (0..n).map(|choice| score_against_all(&|_| choice)).collect();
+    for (choice, score) in fixed_scores.iter().enumerate() {
+        content.push_str(&format!("  always {:<10} {:>8}\n", game.choices[choice], score));
+    }
+    let best_fixed = (0..n).rev().max_by_key(|choice| fixed_scores[*choice]).unwrap();
+    content.push_str(&format!("  best: always {}, total score {}\n", game.choices[best_fixed], fixed_scores[best_fixed]));
+
+    let best_response = |theirs: GameChoice| (0..n)
+        .rev()
+        .max_by_key(|ours| calculate_score(&game, *ours, theirs))
+        .unwrap();
+
+    content.push_str("\nAdaptive response:\n");
+    for theirs in 0..n {
+        content.push_str(&format!("  {:<10} -> {}\n", game.choices[theirs], game.choices[best_response(theirs)]));
+    }
+    content.push_str(&format!("  total score {}\n", score_against_all(&best_response)));
+
+    Ok(content)
 }
@@ -1,22 +1,16 @@
-use std::collections::HashSet;
-use std::collections::HashMap;
+use std::time::Instant;
 
-use crate::common::{Error, read_non_empty_lines};
+use crate::common::{Error, ToolArgs, read_lines};
 
-fn get_line_parts(line: &String) -> (String, String) {
-    let cs: Vec<char> = line.chars().collect();
-
-    let s1 = String::from_iter(&cs[0..cs.len() / 2]);
-    let s2 = String::from_iter(&cs[cs.len() / 2 ..]);
-
-    (s1, s2)
-}
+// Bit p is set when the item with priority p is present, so a-z use bits
+// 1-26 and A-Z bits 27-52.
+type ItemMask = u64;
 
 fn get_priority(c: char) -> Option<i32> {
-    if c >= 'a' && c <= 'z' {
+    if c.is_ascii_lowercase() {
         Some((c as i32) - ('a' as i32) + 1)
     }
-    else if c >= 'A' && c <= 'Z' {
+    else if c.is_ascii_uppercase() {
         Some((c as i32) - ('A' as i32) + 27)
     }
     else {
@@ -24,108 +18,346 @@ fn get_priority(c: char) -> Option<i32> {
     }
 }
 
-fn get_priorities(s: String) -> HashSet<i32> {
-    let mut priorities = HashSet::new() as HashSet<i32>;
-    let priorities_list =
-        s.chars()
-            .map(get_priority)
-            .filter(|o| o.is_some())
-            .map(|o| o.unwrap());
+#[derive(Debug)]
+struct Rucksack {
+    line_number: usize,
+    items: String,
+}
+
+#[derive(Debug)]
+enum RucksackError {
+    InvalidItem { line: usize, item: char },
+    UnevenCompartments { line: usize, len: usize, compartments: usize },
+    MissingCommonItem { line: usize },
+    AmbiguousCommonItem { line: usize, items: String },
+    MissingBadge { first_line: usize },
+    AmbiguousBadge { first_line: usize, badges: String },
+    IncompleteGroup { first_line: usize, size: usize, group_size: usize },
+}
 
-    for p in priorities_list {
-        priorities.insert(p);
+impl From<RucksackError> for Error {
+    fn from(e: RucksackError) -> Error {
+        Error::General(match e {
+            RucksackError::InvalidItem { line, item } =>
+                format!("Line {}: invalid item [{}]", line, item),
+            RucksackError::UnevenCompartments { line, len, compartments } =>
+                format!("Line {}: {} items can't be split into {} equal compartments", line, len, compartments),
+            RucksackError::MissingCommonItem { line } =>
+                format!("Line {}: no item is in every compartment", line),
+            RucksackError::AmbiguousCommonItem { line, items } =>
+                format!("Line {}: more than one item is in every compartment: {}", line, items),
+            RucksackError::MissingBadge { first_line } =>
+                format!("Group starting at line {}: no common badge", first_line),
+            RucksackError::AmbiguousBadge { first_line, badges } =>
+                format!("Group starting at line {}: more than one common badge: {}", first_line, badges),
+            RucksackError::IncompleteGroup { first_line, size, group_size } =>
+                format!("Group starting at line {}: has {} rucksacks, expected {}", first_line, size, group_size),
+        })
     }
+}
 
-    priorities
+fn get_item_mask(s: &str, line: usize) -> Result<ItemMask, RucksackError> {
+    s.chars().try_fold(0, |mask, item| match get_priority(item) {
+        Some(p) => Ok(mask | (1 << p)),
+        None => Err(RucksackError::InvalidItem { line, item })
+    })
 }
 
-fn get_common_priority(is1: &HashSet<i32>, is2: &HashSet<i32>) -> Option<i32> {
-    let samep: Vec<i32> = is1.intersection(is2).map(|i| *i).collect();
+fn describe_items(mask: ItemMask) -> String {
+    ('a'..='z')
+        .chain('A'..='Z')
+        .filter(|c| mask & (1 << get_priority(*c).unwrap()) != 0)
+        .collect()
+}
 
-    if samep.len() == 1 {
-        Some(*samep.get(0).unwrap())
+// The priority of the only item in mask, if there is exactly one.
+fn get_single_priority(mask: ItemMask) -> Option<i32> {
+    if mask.count_ones() == 1 {
+        Some(mask.trailing_zeros() as i32)
     }
     else {
         None
     }
 }
 
-pub fn part1(input_path: &str) -> Result<String, Error> {
-    let lines = read_non_empty_lines(input_path)?;
-    let common_p =
-        (&lines)
+// The items found in every compartment, from a single pass over the line.
+fn get_common_mask(rucksack: &Rucksack, compartments: usize) -> Result<ItemMask, RucksackError> {
+    let line = rucksack.line_number;
+    let len = rucksack.items.len();
+    let even = len > 0 && len.is_multiple_of(compartments);
+    // An uneven line is still read in one piece so an invalid item is reported first.
+    let chunk_len = if even { len / compartments } else { len.max(1) };
+
+    // Valid items are ASCII letters, so the first invalid byte starts the
+    // offending char.
+    let mut common = ItemMask::MAX;
+    for (chunk_idx, chunk) in rucksack.items.as_bytes().chunks(chunk_len).enumerate() {
+        let mut part: ItemMask = 0;
+        for (offset, byte) in chunk.iter().enumerate() {
+            match get_priority(*byte as char) {
+                Some(p) => part |= 1 << p,
+                None => {
+                    let item = rucksack.items[chunk_idx * chunk_len + offset..].chars().next().unwrap();
+                    return Err(RucksackError::InvalidItem { line, item });
+                }
+            }
+        }
+        common &= part;
+    }
+
+    if !even {
+        return Err(RucksackError::UnevenCompartments { line, len, compartments });
+    }
+    Ok(common)
+}
+
+fn get_common_priority(rucksack: &Rucksack, compartments: usize) -> Result<i32, RucksackError> {
+    let line = rucksack.line_number;
+    let mask = get_common_mask(rucksack, compartments)?;
+
+    match get_single_priority(mask) {
+        Some(p) => Ok(p),
+        None if mask == 0 => Err(RucksackError::MissingCommonItem { line }),
+        None => Err(RucksackError::AmbiguousCommonItem { line, items: describe_items(mask) })
+    }
+}
+
+fn get_badge_priority(group: &[Rucksack], group_size: usize) -> Result<i32, RucksackError> {
+    let first_line = group[0].line_number;
+    if group.len() != group_size {
+        return Err(RucksackError::IncompleteGroup { first_line, size: group.len(), group_size });
+    }
+
+    let mut mask = ItemMask::MAX;
+    for rucksack in group {
+        mask &= get_item_mask(&rucksack.items, rucksack.line_number)?;
+    }
+
+    match get_single_priority(mask) {
+        Some(p) => Ok(p),
+        None if mask == 0 => Err(RucksackError::MissingBadge { first_line }),
+        None => Err(RucksackError::AmbiguousBadge { first_line, badges: describe_items(mask) })
+    }
+}
+
+fn part1_total(rucksacks: &[Rucksack], compartments: usize) -> Result<i32, RucksackError> {
+    rucksacks
         .iter()
-        .map(get_line_parts)
-        .map(|(p1, p2)| (get_priorities(p1), get_priorities(p2)))
-        .map(|(s1, s2)| get_common_priority(&s1, &s2));
+        .map(|rucksack| get_common_priority(rucksack, compartments))
+        .sum()
+}
 
-    let result: i32 = common_p.map(|o| o.unwrap_or(0)).sum();
+fn part2_total(rucksacks: &[Rucksack], group_size: usize) -> Result<i32, RucksackError> {
+    rucksacks
+        .chunks(group_size)
+        .map(|group| get_badge_priority(group, group_size))
+        .sum()
+}
 
-    Ok(result.to_string())
+fn read_rucksacks(input_path: &str) -> Result<Vec<Rucksack>, Error> {
+    Ok(read_lines(input_path)?
+        .into_iter()
+        .enumerate()
+        .filter(|(_, line)| !line.is_empty())
+        .map(|(idx, items)| Rucksack { line_number: idx + 1, items })
+        .collect())
 }
 
-fn lines_to_line_groups(lines: Vec<String>) -> Vec<Vec<String>> {
-    let mut line_groups: Vec<Vec<String>> = Vec::new();
-    let mut line_group: Vec<String> = Vec::new();
+pub fn part1(input_path: &str) -> Result<String, Error> {
+    let rucksacks = read_rucksacks(input_path)?;
+    Ok(part1_total(&rucksacks, 2)?.to_string())
+}
 
-    for line in lines {
-        line_group.push(line);
-        if line_group.len() == 3 {
-            line_groups.push(line_group);
-            line_group = Vec::new();
+pub fn part2(input_path: &str) -> Result<String, Error> {
+    let rucksacks = read_rucksacks(input_path)?;
+    Ok(part2_total(&rucksacks, 3)?.to_string())
+}
+
+// Solves both parts with a custom number of compartments per rucksack and
+// rucksacks per group.
+// Args: [input_path] [--compartments N] [--group-size N]
+pub fn solve(args: &[String]) -> Result<String, Error> {
+    let args = ToolArgs::parse(args, &[], &["--compartments", "--group-size"])?;
+    let path = args.positional_or(0, "puzzles/day3-input.txt");
+    let compartments: usize = args.option_parsed("--compartments", 2)?;
+    let group_size: usize = args.option_parsed("--group-size", 3)?;
+
+    if compartments == 0 || group_size == 0 {
+        return Err(Error::General(String::from("Compartments and group size must be at least 1")));
+    }
+
+    let rucksacks = read_rucksacks(path)?;
+    Ok(format!("compartments {}: {}\ngroups of {}: {}\n",
+        compartments, part1_total(&rucksacks, compartments)?,
+        group_size, part2_total(&rucksacks, group_size)?))
+}
+
+// The original HashSet/HashMap based solution, kept as a baseline for bench.
+mod set_based {
+    use std::collections::HashSet;
+    use std::collections::HashMap;
+
+    use crate::day3::get_priority;
+
+    fn get_line_parts(line: &str) -> (String, String) {
+        let cs: Vec<char> = line.chars().collect();
+
+        let s1 = String::from_iter(&cs[0..cs.len() / 2]);
+        let s2 = String::from_iter(&cs[cs.len() / 2 ..]);
+
+        (s1, s2)
+    }
+
+    fn get_priorities(s: String) -> HashSet<i32> {
+        let mut priorities = HashSet::new() as HashSet<i32>;
+        let priorities_list =
+            s.chars()
+                .filter_map(get_priority);
+
+        for p in priorities_list {
+            priorities.insert(p);
         }
+
+        priorities
     }
 
-    if line_group.len() == 3 {
-        line_groups.push(line_group);
+    fn get_common_priority(is1: &HashSet<i32>, is2: &HashSet<i32>) -> Option<i32> {
+        let samep: Vec<i32> = is1.intersection(is2).copied().collect();
+
+        if samep.len() == 1 {
+            Some(*samep.first().unwrap())
+        }
+        else {
+            None
+        }
     }
 
-    line_groups
-}
+    pub fn part1_total(lines: &[String]) -> i32 {
+        let common_p =
+            lines
+            .iter()
+            .map(|line| get_line_parts(line))
+            .map(|(p1, p2)| (get_priorities(p1), get_priorities(p2)))
+            .map(|(s1, s2)| get_common_priority(&s1, &s2));
 
-fn get_label_for_line_group(line_group: &Vec<String>) -> Option<char> {
+        common_p.map(|o| o.unwrap_or(0)).sum()
+    }
 
-    let mut char_to_holder: HashMap<char, HashSet<usize>> = HashMap::new();
+    fn lines_to_line_groups(lines: Vec<String>) -> Vec<Vec<String>> {
+        let mut line_groups: Vec<Vec<String>> = Vec::new();
+        let mut line_group: Vec<String> = Vec::new();
 
-    for (idx, line) in line_group.iter().enumerate() {
-        for c in line.chars() {
-            match char_to_holder.get_mut(&c) {
-                None => {
-                    let mut items: HashSet<usize> = HashSet::new();
-                    items.insert(idx);
-                    char_to_holder.insert(c, items);
-                },
-                Some(items) => {
-                    items.insert(idx);
-                }
+        for line in lines {
+            line_group.push(line);
+            if line_group.len() == 3 {
+                line_groups.push(line_group);
+                line_group = Vec::new();
             }
         }
-    }
 
-    let matching_labels: Vec<char> = char_to_holder
-        .iter()
-        .filter(|(_, owners)| owners.len() == line_group.len())
-        .map(|(c, _)| *c)
-        .collect();
-        
-    if matching_labels.len() == 1 {
-        Some(*matching_labels.get(0).unwrap())
+        if line_group.len() == 3 {
+            line_groups.push(line_group);
+        }
+
+        line_groups
     }
-    else {
-        None
+
+    fn get_label_for_line_group(line_group: &[String]) -> Option<char> {
+
+        let mut char_to_holder: HashMap<char, HashSet<usize>> = HashMap::new();
+
+        for (idx, line) in line_group.iter().enumerate() {
+            for c in line.chars() {
+                match char_to_holder.get_mut(&c) {
+                    None => {
+                        let mut items: HashSet<usize> = HashSet::new();
+                        items.insert(idx);
+                        char_to_holder.insert(c, items);
+                    },
+                    Some(items) => {
+                        items.insert(idx);
+                    }
+                }
+            }
+        }
+
+        let matching_labels: Vec<char> = char_to_holder
+            .iter()
+            .filter(|(_, owners)| owners.len() == line_group.len())
+            .map(|(c, _)| *c)
+            .collect();
+
+        if matching_labels.len() == 1 {
+            Some(*matching_labels.first().unwrap())
+        }
+        else {
+            None
+        }
+
     }
 
+    pub fn part2_total(lines: Vec<String>) -> i32 {
+        let line_groups = lines_to_line_groups(lines);
+
+        line_groups
+            .iter()
+            .map(|line_group| get_label_for_line_group(line_group).unwrap())
+            .map(get_priority)
+            .map(|o| o.unwrap_or(0))
+            .sum()
+    }
 }
 
-pub fn part2(input_path: &str) -> Result<String, Error> {
-    let line_groups = lines_to_line_groups(read_non_empty_lines(input_path)?);
+// A result and the average seconds per run that produced it.
+type Timing<T> = (T, f64);
 
-    let result: i32 = line_groups
-        .iter()
-        .map(|line_group| get_label_for_line_group(line_group).unwrap())
-        .map(get_priority)
-        .map(|o| o.unwrap_or(0))
-        .sum();
-        
-    Ok(result.to_string())
+// Each run gets its own input from make_input, all made before the clock
+// starts, so only f is timed.
+fn time_runs<I, T>(iterations: u32, make_input: impl Fn() -> I, f: impl Fn(I) -> T) -> Timing<T> {
+    let mut inputs: Vec<I> = (0..iterations).map(|_| make_input()).collect();
+    let last = inputs.pop().unwrap();
+
+    let start = Instant::now();
+    for input in inputs {
+        f(input);
+    }
+    let result = f(last);
+    (result, start.elapsed().as_secs_f64() / iterations as f64)
 }
+
+// Times the bitset and set-based engines on the same input.
+// Args: [input_path] [--iterations N]
+pub fn bench(args: &[String]) -> Result<String, Error> {
+    let args = ToolArgs::parse(args, &[], &["--iterations"])?;
+    let path = args.positional_or(0, "puzzles/day3-input.txt");
+    let iterations: u32 = args.option_parsed("--iterations", 200)?.max(1);
+    let rucksacks = read_rucksacks(path)?;
+    let lines: Vec<String> = rucksacks.iter().map(|r| r.items.clone()).collect();
+
+    // Validate once up front so the timed closures can't fail.
+    part1_total(&rucksacks, 2)?;
+    part2_total(&rucksacks, 3)?;
+
+    let runs: Vec<(&str, Timing<i32>, Timing<i32>)> = vec![
+        ("part1",
+            time_runs(iterations, || &rucksacks, |r| part1_total(r, 2).unwrap_or(0)),
+            time_runs(iterations, || &lines, set_based::part1_total)),
+        ("part2",
+            time_runs(iterations, || &rucksacks, |r| part2_total(r, 3).unwrap_or(0)),
+            time_runs(iterations, || lines.clone(), set_based::part2_total)),
+    ];
+
+    let mut content = format!("{} lines, {} iterations\n", lines.len(), iterations);
+    content.push_str(&format!("{:<6} {:>14} {:>14} {:>9}\n", "part", "bitset (ms)", "sets (ms)", "speedup"));
+
+    for (name, (bits_result, bits_secs), (sets_result, sets_secs)) in runs {
+        if bits_result != sets_result {
+            return Err(Error::General(format!(
+                "Engines disagree on {}: bitset {} vs sets {}", name, bits_result, sets_result)));
+        }
+        content.push_str(&format!("{:<6} {:>14.4} {:>14.4} {:>8.1}x\n",
+            name, bits_secs * 1000.0, sets_secs * 1000.0, sets_secs / bits_secs));
+    }
+
+    Ok(content)
+}
\ No newline at end of file
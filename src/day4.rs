@@ -1,51 +1,52 @@
-use crate::common::{Error, read_non_empty_lines};
-use std::num::ParseIntError;
+use crate::common::{Error, Interval, ToolArgs, read_non_empty_lines};
+use crate::common::interval::merge;
+use crate::common::parser::{integer, pair, parse_all, separated_list, tag, terminated};
 
-type WorkRange = (i32, i32);
+type WorkRange = Interval;
 type WorkPair = (WorkRange, WorkRange);
+type WorkGroup = Vec<WorkRange>;
 
-fn parse_line(line: &String) -> Result<WorkPair, Error> {
-    let raw_parts: Vec<&str> = line.split(|c| c == ',' || c =='-').collect();
-    if raw_parts.len() != 4 {
-        Err(Error::General(format!("Invalid line: [{}]", line)))
-    }
-    else {
-        let parts = raw_parts
-            .iter()
-            .map(|p| p.parse::<i32>())
-            .collect::<Result<Vec<i32>, ParseIntError>>()
-            .map_err(|e| Error::General(format!("Invalid number: {}", e)))?;
-
-        Ok((
-            (parts[0], parts[1]),
-            (parts[2], parts[3])
-        ))
-    }
+// Any number of comma separated a-b ranges, one per elf.
+fn parse_group(line: &str) -> Result<WorkGroup, Error> {
+    let range = pair(terminated(integer(), tag("-")), integer());
+    parse_all(separated_list(range, tag(",")), line)?
+        .into_iter()
+        .map(|(start, end)| Interval::new(start, end))
+        .collect()
 }
 
-fn is_contained(p1: WorkRange, p2: WorkRange) -> bool {
-    p2.0 >= p1.0 && p2.1 <= p1.1
+fn parse_line(line: &str) -> Result<WorkPair, Error> {
+    match parse_group(line)?.as_slice() {
+        [a, b] => Ok((*a, *b)),
+        group => Err(Error::General(format!("Expected 2 assignments, found {} in line [{}]", group.len(), line)))
+    }
 }
 
 fn is_one_contained(pair: WorkPair) -> bool {
-    is_contained(pair.0, pair.1) ||
-    is_contained(pair.1, pair.0)
+    pair.0.contains_interval(&pair.1) ||
+    pair.1.contains_interval(&pair.0)
 }
 
 fn is_overlap(pair: WorkPair) -> bool {
-    is_one_contained(pair) ||
-    (pair.0.0 >= pair.1.0 && pair.0.0 <= pair.1.1) ||
-    (pair.0.1 >= pair.1.0 && pair.0.1 <= pair.1.1)
-
+    pair.0.overlaps(&pair.1)
 }
 
-fn do_part_with_condition(input_path: &str, cond: fn(WorkPair) -> bool) -> Result<String, Error> {
-    let lines = read_non_empty_lines(input_path)?;
+fn read_work_pairs(input_path: &str) -> Result<Vec<WorkPair>, Error> {
+    read_non_empty_lines(input_path)?
+        .iter()
+        .map(|line| parse_line(line))
+        .collect()
+}
 
-    let work_pairs = (&lines)
+fn read_work_groups(input_path: &str) -> Result<Vec<WorkGroup>, Error> {
+    read_non_empty_lines(input_path)?
         .iter()
-        .map(parse_line)
-        .collect::<Result<Vec<WorkPair>, Error>>()?;
+        .map(|line| parse_group(line))
+        .collect()
+}
+
+fn do_part_with_condition(input_path: &str, cond: fn(WorkPair) -> bool) -> Result<String, Error> {
+    let work_pairs = read_work_pairs(input_path)?;
 
     let result: i32 = work_pairs
         .iter()
@@ -62,3 +63,421 @@ pub fn part1(input_path: &str) -> Result<String, Error> {
 pub fn part2(input_path: &str) -> Result<String, Error> {
     do_part_with_condition(input_path, is_overlap)
 }
+
+#[derive(Debug)]
+struct Coverage {
+    assignments: usize,
+    bounds: Interval,
+    covered: u128,
+    covered_more_than_once: u128,
+    max_concurrent: usize,
+    max_concurrent_at: Vec<Interval>,
+    gaps: Vec<Interval>,
+}
+
+// Sweeps over the start and end events of every assignment, so the cost is
+// dominated by sorting 2n events rather than by the size of the sections.
+// Positions are i128 because an assignment can end at i64::MAX.
+fn sweep_coverage(assignments: &[Interval]) -> Option<Coverage> {
+    let mut events: Vec<(i128, i64)> = Vec::with_capacity(assignments.len() * 2);
+    for a in assignments {
+        events.push((a.start() as i128, 1));
+        events.push((a.end() as i128 + 1, -1));
+    }
+    events.sort_unstable();
+
+    // Every segment ends before the last event, so its bounds fit in i64.
+    let section = |pos: i128| i64::try_from(pos).ok();
+    let first = section(events.first()?.0)?;
+    let last = section(events.last()?.0 - 1)?;
+
+    let mut coverage = Coverage {
+        assignments: assignments.len(),
+        bounds: Interval::new(first, last).ok()?,
+        covered: 0,
+        covered_more_than_once: 0,
+        max_concurrent: 0,
+        max_concurrent_at: Vec::new(),
+        gaps: Vec::new(),
+    };
+
+    let mut depth: i64 = 0;
+    let mut idx = 0;
+    while idx < events.len() {
+        let pos = events[idx].0;
+        while idx < events.len() && events[idx].0 == pos {
+            depth += events[idx].1;
+            idx += 1;
+        }
+        if idx == events.len() {
+            break;
+        }
+
+        // depth holds for every section in pos..next.
+        let segment = Interval::new(section(pos)?, section(events[idx].0 - 1)?).ok()?;
+        let depth = depth as usize;
+
+        if depth == 0 {
+            coverage.gaps.push(segment);
+        }
+        else {
+            coverage.covered += segment.len();
+            if depth > 1 {
+                coverage.covered_more_than_once += segment.len();
+            }
+        }
+
+        if depth > coverage.max_concurrent {
+            coverage.max_concurrent = depth;
+            coverage.max_concurrent_at.clear();
+        }
+        if depth == coverage.max_concurrent {
+            coverage.max_concurrent_at.push(segment);
+        }
+    }
+
+    coverage.max_concurrent_at = merge(&coverage.max_concurrent_at);
+    Some(coverage)
+}
+
+fn describe_intervals(intervals: &[Interval], limit: usize) -> String {
+    let mut parts: Vec<String> = intervals.iter().take(limit).map(|i| i.to_string()).collect();
+    if intervals.len() > limit {
+        parts.push(format!("... {} more", intervals.len() - limit));
+    }
+    if parts.is_empty() {
+        String::from("none")
+    }
+    else {
+        parts.join(", ")
+    }
+}
+
+// Reports how all section assignments in the file cover the sections.
+// Args: [input_path]
+pub fn coverage(args: &[String]) -> Result<String, Error> {
+    let args = ToolArgs::parse(args, &[], &[])?;
+    let path = args.positional_or(0, "puzzles/day4-input.txt");
+
+    let assignments: Vec<Interval> = read_work_groups(path)?
+        .into_iter()
+        .flatten()
+        .collect();
+
+    let coverage = sweep_coverage(&assignments)
+        .ok_or_else(|| Error::General(format!("No assignments in {}", path)))?;
+
+    let gap_sections: u128 = coverage.gaps.iter().map(|g| g.len()).sum();
+
+    Ok(format!(
+        "Assignments: {}\n\
+        Sections: {} ({} in range)\n\
+        Covered: {}\n\
+        Covered by more than one elf: {}\n\
+        Max concurrent coverage: {} at {}\n\
+        Uncovered gaps: {} sections in {}\n",
+        coverage.assignments,
+        coverage.bounds, coverage.bounds.len(),
+        coverage.covered,
+        coverage.covered_more_than_once,
+        coverage.max_concurrent, describe_intervals(&coverage.max_concurrent_at, 10),
+        gap_sections, describe_intervals(&coverage.gaps, 10)))
+}
+
+struct Elf {
+    label: String,
+    range: WorkRange,
+}
+
+// Elves connected when their assignments overlap. For intervals the
+// components and cliques fall out of sorting and sweeping, so no pairwise
+// comparison is needed outside of the matrix.
+struct OverlapGraph {
+    elves: Vec<Elf>,
+}
+
+const MATRIX_LIMIT: usize = 64;
+
+impl OverlapGraph {
+    fn new(elves: Vec<Elf>) -> OverlapGraph {
+        OverlapGraph { elves }
+    }
+
+    fn overlaps(&self, a: usize, b: usize) -> bool {
+        self.elves[a].range.overlaps(&self.elves[b].range)
+    }
+
+    // Largest components first, each with the sections it spans.
+    fn components(&self) -> Vec<(Interval, Vec<usize>)> {
+        let mut order: Vec<usize> = (0..self.elves.len()).collect();
+        order.sort_unstable_by_key(|idx| self.elves[*idx].range);
+
+        let mut components: Vec<(Interval, Vec<usize>)> = Vec::new();
+        for idx in order {
+            let range = self.elves[idx].range;
+            match components.last_mut() {
+                Some((span, members)) if span.overlaps(&range) => {
+                    *span = span.union(&range)[0];
+                    members.push(idx);
+                },
+                _ => components.push((range, vec![idx]))
+            }
+        }
+
+        for (_, members) in components.iter_mut() {
+            members.sort_unstable();
+        }
+        components.sort_by_key(|(span, members)| (std::cmp::Reverse(members.len()), *span));
+        components
+    }
+
+    // Intervals that overlap pairwise all share a section, so the largest
+    // clique is every elf covering the most covered section.
+    fn largest_clique(&self) -> Option<(i64, Vec<usize>)> {
+        let ranges: Vec<Interval> = self.elves.iter().map(|e| e.range).collect();
+        let section = sweep_coverage(&ranges)?.max_concurrent_at.first()?.start();
+
+        let members = (0..self.elves.len())
+            .filter(|idx| self.elves[*idx].range.contains(section))
+            .collect();
+        Some((section, members))
+    }
+
+    fn matrix(&self) -> String {
+        if self.elves.len() > MATRIX_LIMIT {
+            return format!("Matrix skipped: {} elves (limit {})\n", self.elves.len(), MATRIX_LIMIT);
+        }
+
+        let width = self.elves.iter().map(|e| e.label.len()).max().unwrap_or(0);
+        let mut content = String::new();
+
+        for a in 0..self.elves.len() {
+            let cells: Vec<&str> = (0..self.elves.len())
+                .map(|b| if a == b { "\\" } else if self.overlaps(a, b) { "#" } else { "." })
+                .collect();
+            content.push_str(&format!(
+                "{:>width$} {:<11} {}\n",
+                self.elves[a].label, self.elves[a].range.to_string(), cells.join(""), width = width));
+        }
+        content
+    }
+
+    fn describe(&self, members: &[usize], limit: usize) -> String {
+        let mut labels: Vec<&str> = members.iter().take(limit).map(|idx| self.elves[*idx].label.as_str()).collect();
+        let more = format!("... {} more", members.len().saturating_sub(limit));
+        if members.len() > limit {
+            labels.push(&more);
+        }
+        labels.join(", ")
+    }
+
+    fn report(&self, with_matrix: bool) -> String {
+        let mut content = String::new();
+
+        let components = self.components();
+        content.push_str(&format!("Components: {}\n", components.len()));
+        for (span, members) in components.iter().take(10) {
+            content.push_str(&format!("  {} elves over {}: {}\n", members.len(), span, self.describe(members, 10)));
+        }
+        if components.len() > 10 {
+            content.push_str(&format!("  ... {} more\n", components.len() - 10));
+        }
+
+        if let Some((section, members)) = self.largest_clique() {
+            content.push_str(&format!(
+                "Largest clique: {} elves sharing section {}: {}\n",
+                members.len(), section, self.describe(&members, 10)));
+        }
+
+        if with_matrix {
+            content.push_str(&self.matrix());
+        }
+        content
+    }
+}
+
+// Builds an overlap graph of the elves in each line, or of every elf in the
+// file, and reports its components, largest clique and overlap matrix.
+// Args: [input_path] [--scope line|file] [--matrix]
+pub fn groups(args: &[String]) -> Result<String, Error> {
+    let args = ToolArgs::parse(args, &["--matrix"], &["--scope"])?;
+    let path = args.positional_or(0, "puzzles/day4-input.txt");
+    let with_matrix = args.flag("--matrix");
+
+    let groups = read_work_groups(path)?;
+
+    match args.option("--scope").unwrap_or("line") {
+        "line" => {
+            let mut content = String::new();
+            for (idx, group) in groups.iter().enumerate() {
+                let elves = group
+                    .iter()
+                    .enumerate()
+                    .map(|(elf, range)| Elf { label: (elf + 1).to_string(), range: *range })
+                    .collect();
+                content.push_str(&format!("Line {}: {} elves\n", idx + 1, group.len()));
+                content.push_str(&OverlapGraph::new(elves).report(with_matrix));
+            }
+            Ok(content)
+        },
+        "file" => {
+            let elves: Vec<Elf> = groups
+                .iter()
+                .enumerate()
+                .flat_map(|(idx, group)| group
+                    .iter()
+                    .enumerate()
+                    .map(move |(elf, range)| Elf { label: format!("{}.{}", idx + 1, elf + 1), range: *range }))
+                .collect();
+            Ok(format!("{} elves\n{}", elves.len(), OverlapGraph::new(elves).report(with_matrix)))
+        },
+        scope => Err(Error::General(format!("Invalid value for --scope: {} (expected line or file)", scope)))
+    }
+}
+
+const ANSI_OVERLAP: &str = "\x1b[1;31m";
+const ANSI_RESET: &str = "\x1b[0m";
+
+// Draws assignments as `.234.....` strips over a window of sections. When the
+// window is wider than the strip, each column stands for `scale` sections.
+struct Strip {
+    window: Interval,
+    // u128 like Interval::len, so the full i64 range can be scaled.
+    scale: u128,
+    color: bool,
+}
+
+impl Strip {
+    fn new(window: Interval, width: usize, color: bool) -> Strip {
+        Strip { window, scale: window.len().div_ceil(width as u128).max(1), color }
+    }
+
+    fn columns(&self) -> impl Iterator<Item = Interval> {
+        let end = self.window.end() as i128;
+        let scale = self.scale as i128;
+        std::iter::successors(Some(self.window.start() as i128), move |start| Some(start + scale).filter(|next| *next <= end))
+            .map(move |start| Interval::new(start as i64, (start + scale - 1).min(end) as i64).unwrap())
+    }
+
+    // Sections shared with another elf of the group are drawn with `#`, or
+    // in colour.
+    fn render(&self, range: &Interval, overlaps: &[Interval]) -> String {
+        let mut content = String::new();
+        for column in self.columns() {
+            if !range.overlaps(&column) {
+                content.push('.');
+                continue;
+            }
+
+            let glyph = if self.scale == 1 {
+                char::from_digit(column.start().rem_euclid(10) as u32, 10).unwrap()
+            }
+            else {
+                '='
+            };
+            let is_overlap = overlaps
+                .iter()
+                .filter_map(|o| o.intersection(range))
+                .any(|o| o.overlaps(&column));
+
+            match (is_overlap, self.color) {
+                (false, _) => content.push(glyph),
+                (true, false) => content.push('#'),
+                (true, true) => content.push_str(&format!("{}{}{}", ANSI_OVERLAP, glyph, ANSI_RESET))
+            }
+        }
+        content
+    }
+}
+
+// Sections covered by at least two elves of the group.
+fn group_overlaps(group: &[WorkRange]) -> Vec<Interval> {
+    let mut overlaps: Vec<Interval> = Vec::new();
+    for (idx, a) in group.iter().enumerate() {
+        overlaps.extend(group[idx + 1..].iter().filter_map(|b| a.intersection(b)));
+    }
+    merge(&overlaps)
+}
+
+fn terminal_width() -> usize {
+    std::env::var("COLUMNS")
+        .ok()
+        .and_then(|c| c.parse::<usize>().ok())
+        .unwrap_or(80)
+}
+
+// Draws every line's assignments as aligned strips, highlighting the sections
+// that overlap. The window defaults to all sections in the file and is scaled
+// down to fit the width ($COLUMNS, or 80).
+// Args: [input_path] [--window A-B] [--width N] [--color]
+pub fn render(args: &[String]) -> Result<String, Error> {
+    let args = ToolArgs::parse(args, &["--color"], &["--window", "--width"])?;
+    let path = args.positional_or(0, "puzzles/day4-input.txt");
+
+    let groups = read_work_groups(path)?;
+
+    let window = match args.option("--window") {
+        Some(value) => match parse_group(value)?.as_slice() {
+            [window] => *window,
+            _ => return Err(Error::General(format!("Invalid value for --window: {}", value)))
+        },
+        None => {
+            let sections = merge(&groups.iter().flatten().copied().collect::<Vec<Interval>>());
+            match (sections.first(), sections.last()) {
+                (Some(first), Some(last)) => Interval::new(first.start(), last.end())?,
+                _ => return Err(Error::General(format!("No assignments in {}", path)))
+            }
+        }
+    };
+
+    let labels: Vec<Vec<String>> = groups
+        .iter()
+        .enumerate()
+        .map(|(idx, group)| group
+            .iter()
+            .enumerate()
+            .map(|(elf, range)| format!("{}.{} {}", idx + 1, elf + 1, range))
+            .collect())
+        .collect();
+    let label_width = labels.iter().flatten().map(|l| l.len()).max().unwrap_or(0);
+
+    let width: usize = args.option_parsed("--width", terminal_width())?;
+    let strip = Strip::new(window, width.saturating_sub(label_width + 1).max(10), args.flag("--color"));
+
+    let mut content = format!("Sections {}, {} per column\n", window, strip.scale);
+    for (group, labels) in groups.iter().zip(&labels) {
+        let overlaps = group_overlaps(group);
+        content.push('\n');
+        for (range, label) in group.iter().zip(labels) {
+            content.push_str(&format!("{:<width$} {}\n", label, strip.render(range, &overlaps), width = label_width));
+        }
+    }
+    Ok(content)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn coverage_up_to_i64_max() {
+        let assignments = [Interval::new(1, i64::MAX).unwrap(), Interval::new(1, 2).unwrap()];
+        let coverage = sweep_coverage(&assignments).unwrap();
+        assert_eq!(coverage.bounds, assignments[0]);
+        assert_eq!(coverage.covered, i64::MAX as u128);
+        assert_eq!(coverage.covered_more_than_once, 2);
+        assert!(coverage.gaps.is_empty());
+    }
+
+    #[test]
+    fn strip_over_full_range() {
+        let full = Interval::new(i64::MIN, i64::MAX).unwrap();
+        let strip = Strip::new(full, 1, false);
+        assert_eq!(strip.columns().collect::<Vec<Interval>>(), vec![full]);
+
+        let strip = Strip::new(full, 4, false);
+        assert_eq!(strip.columns().count(), 4);
+        assert_eq!(strip.render(&Interval::new(1, 2).unwrap(), &[]), "..=.");
+    }
+}
@@ -1,159 +1,997 @@
-use crate::common::{Error, read_lines};
-use regex::{Captures, Regex};
+use std::fmt::{Display, Formatter, Result as FmtResult};
+use std::thread::sleep;
+use std::time::{Duration, Instant};
+
+use crate::common::{Error, Interval, Normalization, ToolArgs, read_lines_with};
+use crate::common::random::Rng;
+use crate::common::regex_parse::regex_struct;
+use regex::Regex;
 use lazy_static::lazy_static;
 
-type CargoStacks = Vec<Vec<char>>;
+// The text between the brackets of a crate, e.g. `A` or `XY`.
+type Crate = String;
+type CargoStacks = Vec<Vec<Crate>>;
+
+// The crate drawing pads rows with trailing spaces, so those are expected.
+const INPUT_NORMALIZATION: Normalization = Normalization {
+    trim_trailing_whitespace: false,
+    ..Normalization::STANDARD
+};
+
+regex_struct! {
+    //move 1 from 7 to 4
+    #[derive(Debug, PartialEq)]
+    struct MoveOperation = r"^move (\d+) from (\S+) to (\S+)\s*$" {
+        n: u32,
+        from: String,
+        to: String,
+    }
+}
+
+impl Display for MoveOperation {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        write!(f, "move {} from {} to {}", self.n, self.from, self.to)
+    }
+}
 
-#[derive(Debug)]
-struct MoveOperation {
-    n: u32,
+// A move and the input line it was read from.
+type NumberedMove = (usize, MoveOperation);
+
+// A move with its stack labels resolved to indices into the stacks.
+#[derive(Debug, Clone, Copy)]
+struct StackMove {
+    n: usize,
+    from: usize,
+    to: usize,
+}
+
+// Undoes a move that took n crates from `from` to `to`, per_lift at a time:
+// the lifts are taken back off `to` in the opposite order.
+#[derive(Debug, Clone, Copy)]
+struct UndoMove {
     from: usize,
     to: usize,
+    n: usize,
+    per_lift: usize,
+}
+
+impl UndoMove {
+    fn apply(&self, cargo_stacks: &mut [Vec<Crate>]) -> Result<(), String> {
+        let available = cargo_stacks[self.to].len();
+        if available < self.n {
+            return Err(format!("stack only holds {} crates, {} needed to undo the move", available, self.n));
+        }
+
+        // Only the last lift can be short.
+        let mut lift = match self.n % self.per_lift {
+            0 => self.per_lift,
+            short => short
+        };
+        let mut remaining = self.n;
+
+        while remaining > 0 {
+            move_crates(cargo_stacks, self.to, self.from, lift, false);
+            remaining -= lift;
+            lift = self.per_lift.min(remaining);
+        }
+        Ok(())
+    }
 }
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq)]
 struct Input {
+    labels: Vec<String>,
     cargo_stacks: CargoStacks,
-    operations: Vec<MoveOperation>,
+    operations: Vec<NumberedMove>,
 }
 
-lazy_static! {
-    static ref STACK_PLACEMENT_RE: Regex = Regex::new(r"^(\s*\[[A-Z]\])+\s*$").unwrap();
+// Writes the input back out in the puzzle format, which parse_input reads
+// into an equal Input.
+impl Display for Input {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        write!(f, "{}", draw_stacks(&self.labels, &self.cargo_stacks))?;
+        writeln!(f)?;
+        for (_, op) in &self.operations {
+            writeln!(f, "{}", op)?;
+        }
+        Ok(())
+    }
 }
 
 lazy_static! {
-    static ref LABEL_LINE_RE: Regex = Regex::new(r"^(\s+\d)+\s*$").unwrap();
+    static ref CRATE_ROW_RE: Regex = Regex::new(r"^\s*(\[[^\[\]\s]+\]\s*)+$").unwrap();
+    static ref CRATE_RE: Regex = Regex::new(r"\[([^\[\]\s]+)\]").unwrap();
+    static ref LABEL_RE: Regex = Regex::new(r"\S+").unwrap();
 }
 
-lazy_static! {
-    //move 1 from 7 to 4
-    static ref MOVE_LINE_RE: Regex = Regex::new(r"^move (\d+) from (\d+) to (\d+)\s*$").unwrap();
+// The columns a regex match covers, counted in characters.
+fn columns(line: &str, start: usize, end: usize) -> Interval {
+    let first = line[..start].chars().count() as i64;
+    let last = first + line[start..end].chars().count() as i64 - 1;
+    Interval::new(first, last).unwrap()
 }
 
-fn ensure_n_stacks(cargo_stacks: &mut Vec<Vec<char>>, stack_id: usize) {
-    while stack_id >= cargo_stacks.len() {
-        cargo_stacks.push(Vec::new())
+fn parse_label_line(line: &str, line_number: usize) -> Result<Vec<(Interval, String)>, Error> {
+    let mut labels: Vec<(Interval, String)> = Vec::new();
+
+    for m in LABEL_RE.find_iter(line) {
+        if labels.iter().any(|(_, label)| label == m.as_str()) {
+            return Err(Error::General(format!("Duplicate stack label {} on line {}", m.as_str(), line_number)));
+        }
+        labels.push((columns(line, m.start(), m.end()), String::from(m.as_str())));
     }
-}
 
-fn add_to_stack(cargo_stacks: &mut Vec<Vec<char>>, c: char, stack_id: usize) {
-    ensure_n_stacks(cargo_stacks, stack_id);
-    cargo_stacks[stack_id].push(c);
+    Ok(labels)
 }
 
-fn parse_stack_line(cargo_stacks: &mut Vec<Vec<char>>, line: &String) -> Result<(), Error> {
-    for (idx, c) in line.chars().enumerate() {
-        if c >= 'A' && c <= 'Z' {
-            let stack = idx / 4;
-            add_to_stack(cargo_stacks, c, stack);
+// Each crate goes on the stack whose label it sits above; when it spans
+// several labels, the one closest to its middle.
+fn parse_crate_row(labels: &[(Interval, String)], cargo_stacks: &mut CargoStacks, line: &str, line_number: usize) -> Result<(), Error> {
+    let mut used: Vec<usize> = Vec::new();
+
+    for c in CRATE_RE.captures_iter(line) {
+        let m = c.get(0).unwrap();
+        let span = columns(line, m.start(), m.end());
+        let middle = |i: &Interval| i.start() + i.end();
+
+        let stack = labels
+            .iter()
+            .enumerate()
+            .filter(|(_, (label_span, _))| label_span.overlaps(&span))
+            .min_by_key(|(_, (label_span, _))| (middle(label_span) - middle(&span)).abs())
+            .map(|(idx, _)| idx)
+            .ok_or_else(|| Error::General(format!("Crate {} on line {} is not above a stack label", m.as_str(), line_number)))?;
+
+        if used.contains(&stack) {
+            return Err(Error::General(format!("Two crates above stack {} on line {}", labels[stack].1, line_number)));
         }
+        used.push(stack);
+        cargo_stacks[stack].push(String::from(&c[1]));
     }
     Ok(())
 }
 
-fn parse_stack_label_line(cargo_stacks: &mut Vec<Vec<char>>, line: &String) -> Result<Vec<String>, Error> {
-    let mut labels: Vec<String> = Vec::new();
+// The drawing is the crate rows, top first, followed by the label line that
+// fixes the columns of the stacks. Moves follow after a blank line.
+fn parse_input(lines: &[String]) -> Result<Input, Error> {
+    let rows = lines.iter().take_while(|line| CRATE_ROW_RE.is_match(line)).count();
+
+    let label_line = match lines.get(rows) {
+        Some(line) if !line.trim().is_empty() && MoveOperation::from(line).is_err() => line,
+        _ => return Err(Error::General(format!("Missing stack label line after the crate drawing on line {}", rows + 1)))
+    };
+    let labels = parse_label_line(label_line, rows + 1)?;
 
-    for part in line.split(" ") {
-        if part.len() > 0 {
-            labels.push(String::from(part));
+    let mut cargo_stacks: CargoStacks = vec![Vec::new(); labels.len()];
+    for (idx, line) in lines[..rows].iter().enumerate().rev() {
+        parse_crate_row(&labels, &mut cargo_stacks, line, idx + 1)?;
+    }
+
+    let mut operations: Vec<NumberedMove> = Vec::new();
+    for (idx, line) in lines.iter().enumerate().skip(rows + 1) {
+        if !line.is_empty() {
+            operations.push((idx + 1, MoveOperation::from(line)?));
         }
     }
 
-    ensure_n_stacks(cargo_stacks, labels.len());
+    Ok(Input { labels: labels.into_iter().map(|(_, label)| label).collect(), cargo_stacks, operations })
+}
 
-    Ok(labels)
+// Moves crates between stacks. Moves reaching a crane name existing stacks;
+// n may still be more than `from` holds, in which case only what is there is
+// moved. A successful move returns the record that undoes it, errors are the
+// reason the move was refused.
+trait Crane {
+    fn name(&self) -> String;
+
+    // Called once with the starting layout, before any move is applied.
+    fn start(&mut self, _cargo_stacks: &[Vec<Crate>]) {}
+
+    fn apply(&mut self, cargo_stacks: &mut [Vec<Crate>], op: &StackMove) -> Result<UndoMove, String>;
+
+    // The record that undoes op once all n of its crates have moved.
+    fn inverse(&self, op: &StackMove) -> UndoMove;
+
+    // How many lifts moving n crates takes.
+    fn lifts(&self, n: usize) -> usize;
+
+    fn report(&self) -> Option<String> {
+        None
+    }
+}
+
+fn move_count(cargo_stacks: &[Vec<Crate>], op: &StackMove) -> usize {
+    op.n.min(cargo_stacks[op.from].len())
+}
+
+// The move as it will happen, with n clamped to what `from` holds.
+fn clamped(cargo_stacks: &[Vec<Crate>], op: &StackMove) -> StackMove {
+    StackMove { n: move_count(cargo_stacks, op), ..*op }
 }
 
-fn parse_move_line(operations: &mut Vec<MoveOperation>, c: &Captures) {
-    operations.push(MoveOperation {
-        n: c[1].parse().unwrap(),
-        from: c[2].parse().unwrap(),
-        to: c[3].parse().unwrap()
-    })
+// Mutable references to two different stacks.
+fn stack_pair(cargo_stacks: &mut [Vec<Crate>], a: usize, b: usize) -> (&mut Vec<Crate>, &mut Vec<Crate>) {
+    if a < b {
+        let (left, right) = cargo_stacks.split_at_mut(b);
+        (&mut left[a], &mut right[0])
+    }
+    else {
+        let (left, right) = cargo_stacks.split_at_mut(a);
+        (&mut right[0], &mut left[b])
+    }
 }
 
-fn reverse_stacks(cargo_stacks: &mut Vec<Vec<char>>) {
-    for s in cargo_stacks.iter_mut() {
-        s.reverse();
+// Below this many crates a move is cheaper crate by crate than as a drain.
+const BULK_MOVE_MIN: usize = 16;
+
+// Moves the top n crates of `from` onto `to`, flipping their order when
+// reversed. Large moves are one drain into `to`; small ones pop and push, and
+// reverse what they pushed when the order has to be kept. Moving a stack onto
+// itself changes nothing either way.
+fn move_crates(cargo_stacks: &mut [Vec<Crate>], from: usize, to: usize, n: usize, reversed: bool) {
+    if n < BULK_MOVE_MIN {
+        let at = cargo_stacks[to].len();
+        for _ in 0..n {
+            let c = cargo_stacks[from].pop().unwrap();
+            cargo_stacks[to].push(c);
+        }
+        if !reversed {
+            cargo_stacks[to][at..].reverse();
+        }
+        return;
+    }
+    if from == to {
+        return;
+    }
+
+    let (from, to) = stack_pair(cargo_stacks, from, to);
+    let crates = from.drain(from.len() - n..);
+    if reversed {
+        to.extend(crates.rev());
     }
+    else {
+        to.extend(crates);
+    }
+}
+
+// One crate at a time ends up as the top n in reverse order.
+fn move_stack_9000(cargo_stacks: &mut [Vec<Crate>], op: &StackMove) {
+    let n = move_count(cargo_stacks, op);
+    move_crates(cargo_stacks, op.from, op.to, n, true);
 }
 
-fn parse_input(lines: &Vec<String>) -> Result<Input, Error> {
-    let mut cargo_stacks: Vec<Vec<char>> = Vec::new();
-    let mut operations: Vec<MoveOperation> = Vec::new();
+fn move_stack_9001(cargo_stacks: &mut [Vec<Crate>], op: &StackMove) {
+    let n = move_count(cargo_stacks, op);
+    move_crates(cargo_stacks, op.from, op.to, n, false);
+}
 
-    for line in lines {
-        if STACK_PLACEMENT_RE.is_match(line) {
-            parse_stack_line(&mut cargo_stacks, line)?;
+// The original crate by crate movers, kept as a baseline for bench.
+mod popping {
+    use crate::day5::{Crate, StackMove, move_count};
+
+    pub fn move_stack_9000(cargo_stacks: &mut [Vec<Crate>], op: &StackMove) {
+        let n = move_count(cargo_stacks, op);
+
+        for _ in 0..n {
+            let c = cargo_stacks[op.from].pop().unwrap();
+            cargo_stacks[op.to].push(c);
+        }
+    }
+
+    pub fn move_stack_9001(cargo_stacks: &mut [Vec<Crate>], op: &StackMove) {
+        let n = move_count(cargo_stacks, op);
+        let mut chunk: Vec<Crate> = Vec::new();
+
+        for _ in 0..n {
+            chunk.push(cargo_stacks[op.from].pop().unwrap());
         }
-        else if LABEL_LINE_RE.is_match(line) {
-            parse_stack_label_line(&mut cargo_stacks, line)?;
+
+        chunk.reverse();
+        cargo_stacks[op.to].extend(chunk);
+    }
+}
+
+// CrateMover 9000: one crate per lift.
+struct Crane9000;
+
+impl Crane for Crane9000 {
+    fn name(&self) -> String {
+        String::from("9000")
+    }
+
+    fn apply(&mut self, cargo_stacks: &mut [Vec<Crate>], op: &StackMove) -> Result<UndoMove, String> {
+        let undo = self.inverse(&clamped(cargo_stacks, op));
+        move_stack_9000(cargo_stacks, op);
+        Ok(undo)
+    }
+
+    fn inverse(&self, op: &StackMove) -> UndoMove {
+        UndoMove { from: op.from, to: op.to, n: op.n, per_lift: 1 }
+    }
+
+    fn lifts(&self, n: usize) -> usize {
+        n
+    }
+}
+
+// CrateMover 9001: all crates in one lift, keeping their order.
+struct Crane9001;
+
+impl Crane for Crane9001 {
+    fn name(&self) -> String {
+        String::from("9001")
+    }
+
+    fn apply(&mut self, cargo_stacks: &mut [Vec<Crate>], op: &StackMove) -> Result<UndoMove, String> {
+        let undo = self.inverse(&clamped(cargo_stacks, op));
+        move_stack_9001(cargo_stacks, op);
+        Ok(undo)
+    }
+
+    fn inverse(&self, op: &StackMove) -> UndoMove {
+        UndoMove { from: op.from, to: op.to, n: op.n, per_lift: op.n.max(1) }
+    }
+
+    fn lifts(&self, n: usize) -> usize {
+        n.min(1)
+    }
+}
+
+// Lifts at most per_lift crates at a time, keeping their order within a lift.
+// With 1 it behaves as the 9000, with enough as the 9001.
+struct LimitedCrane {
+    per_lift: usize,
+}
+
+impl Crane for LimitedCrane {
+    fn name(&self) -> String {
+        format!("limited:{}", self.per_lift)
+    }
+
+    fn apply(&mut self, cargo_stacks: &mut [Vec<Crate>], op: &StackMove) -> Result<UndoMove, String> {
+        let undo = self.inverse(&clamped(cargo_stacks, op));
+        let mut remaining = undo.n;
+
+        while remaining > 0 {
+            let k = remaining.min(self.per_lift);
+            move_crates(cargo_stacks, op.from, op.to, k, false);
+            remaining -= k;
         }
-        else if line.len() == 0 {
-            // skip
+        Ok(undo)
+    }
+
+    fn inverse(&self, op: &StackMove) -> UndoMove {
+        UndoMove { from: op.from, to: op.to, n: op.n, per_lift: self.per_lift }
+    }
+
+    fn lifts(&self, n: usize) -> usize {
+        n.div_ceil(self.per_lift)
+    }
+}
+
+// Refuses moves that would leave a stack taller than max_height.
+struct HeightLimitedCrane {
+    max_height: usize,
+    inner: Box<dyn Crane>,
+}
+
+impl Crane for HeightLimitedCrane {
+    fn name(&self) -> String {
+        format!("max-height:{}:{}", self.max_height, self.inner.name())
+    }
+
+    fn apply(&mut self, cargo_stacks: &mut [Vec<Crate>], op: &StackMove) -> Result<UndoMove, String> {
+        let height = if op.from == op.to {
+            cargo_stacks[op.to].len()
         }
         else {
-            match MOVE_LINE_RE.captures(line) {
-                None => {
-                    return Err(Error::General(format!("Invalid line: [{}]", line)));
-                },
-                Some(c) => {
-                    parse_move_line(&mut operations, &c);
-                }
-            }
+            cargo_stacks[op.to].len() + move_count(cargo_stacks, op)
+        };
+
+        if height > self.max_height {
+            return Err(format!("stack would hold {} crates, above the maximum of {}", height, self.max_height));
         }
+        self.inner.apply(cargo_stacks, op)
     }
 
-    reverse_stacks(&mut cargo_stacks);
+    fn start(&mut self, cargo_stacks: &[Vec<Crate>]) {
+        self.inner.start(cargo_stacks);
+    }
 
-    Ok(Input { cargo_stacks, operations })
+    fn inverse(&self, op: &StackMove) -> UndoMove {
+        self.inner.inverse(op)
+    }
+
+    fn lifts(&self, n: usize) -> usize {
+        self.inner.lifts(n)
+    }
+
+    fn report(&self) -> Option<String> {
+        self.inner.report()
+    }
 }
 
-fn move_stack_9000(cargo_stacks: &mut Vec<Vec<char>>, op: &MoveOperation) {
-    let n = (op.n as usize).min(cargo_stacks[op.from - 1].len());
+// Counts the cost of the moves made by another crane.
+struct MetricsCrane {
+    lifts: usize,
+    crates_moved: usize,
+    peak_height: usize,
+    inner: Box<dyn Crane>,
+}
+
+impl Crane for MetricsCrane {
+    fn name(&self) -> String {
+        format!("metrics:{}", self.inner.name())
+    }
+
+    fn start(&mut self, cargo_stacks: &[Vec<Crate>]) {
+        self.peak_height = cargo_stacks.iter().map(|s| s.len()).fold(self.peak_height, usize::max);
+        self.inner.start(cargo_stacks);
+    }
+
+    fn apply(&mut self, cargo_stacks: &mut [Vec<Crate>], op: &StackMove) -> Result<UndoMove, String> {
+        let undo = self.inner.apply(cargo_stacks, op)?;
 
-    for _ in 0..n {
-        let c = cargo_stacks[op.from - 1].pop().unwrap();
-        cargo_stacks[op.to - 1].push(c);
+        self.lifts += self.inner.lifts(undo.n);
+        self.crates_moved += undo.n;
+        self.peak_height = cargo_stacks.iter().map(|s| s.len()).fold(self.peak_height, usize::max);
+        Ok(undo)
+    }
+
+    fn inverse(&self, op: &StackMove) -> UndoMove {
+        self.inner.inverse(op)
+    }
+
+    fn lifts(&self, n: usize) -> usize {
+        self.inner.lifts(n)
+    }
+
+    fn report(&self) -> Option<String> {
+        let own = format!("lifts: {}, crates moved: {}, peak height: {}", self.lifts, self.crates_moved, self.peak_height);
+        match self.inner.report() {
+            Some(inner) => Some(format!("{}\n{}", own, inner)),
+            None => Some(own)
+        }
     }
 }
 
-fn move_stack_9001(cargo_stacks: &mut Vec<Vec<char>>, op: &MoveOperation) {
-    let n = (op.n as usize).min(cargo_stacks[op.from - 1].len());
-    let mut chunk: Vec<char> = Vec::new();
+const CRANE_NAMES: &str = "9000, 9001, limited:K, max-height:H:<crane>, metrics:<crane>";
 
-    for _ in 0..n {
-        chunk.push(cargo_stacks[op.from - 1].pop().unwrap());
+// Cranes by name. The max-height and metrics cranes wrap the crane named
+// after them, e.g. `metrics:max-height:40:9001`.
+fn crane_by_name(name: &str) -> Result<Box<dyn Crane>, Error> {
+    let unknown = || Error::General(format!("Unknown crane: {} (known: {})", name, CRANE_NAMES));
+    let number = |value: &str| value.parse::<usize>().ok().filter(|v| *v > 0).ok_or_else(unknown);
+
+    let (kind, rest) = name.split_once(':').unwrap_or((name, ""));
+    match kind {
+        "9000" if rest.is_empty() => Ok(Box::new(Crane9000)),
+        "9001" if rest.is_empty() => Ok(Box::new(Crane9001)),
+        "limited" => Ok(Box::new(LimitedCrane { per_lift: number(rest)? })),
+        "max-height" => {
+            let (max_height, inner) = rest.split_once(':').ok_or_else(unknown)?;
+            Ok(Box::new(HeightLimitedCrane { max_height: number(max_height)?, inner: crane_by_name(inner)? }))
+        },
+        "metrics" => Ok(Box::new(MetricsCrane { lifts: 0, crates_moved: 0, peak_height: 0, inner: crane_by_name(rest)? })),
+        _ => Err(unknown())
     }
+}
 
-    chunk.reverse();
-    cargo_stacks[op.to - 1].extend(chunk);
+fn read_input(input_path: &str) -> Result<Input, Error> {
+    let lines = read_lines_with(input_path, &INPUT_NORMALIZATION)?;
+    parse_input(&lines)
 }
 
-fn run_part(input_path: &str, mover_fn: fn(&mut Vec<Vec<char>>, &MoveOperation)) -> Result<String, Error> {
-    let lines = read_lines(input_path)?;
-    let mut input = parse_input(&lines)?;
+fn move_error(move_op: &NumberedMove, reason: String) -> Error {
+    Error::General(format!("Invalid move on line {}: [{}]: {}", move_op.0, move_op.1, reason))
+}
 
-    for move_op in &input.operations {
-        mover_fn(&mut input.cargo_stacks, move_op)
+// Both stacks must exist. Unless lenient, `from` must also hold at least n
+// crates; lenient moves clamp to what is there.
+fn resolve_move(labels: &[String], cargo_stacks: &[Vec<Crate>], move_op: &NumberedMove, lenient: bool) -> Result<StackMove, Error> {
+    let op = &move_op.1;
+    let stack = |label: &str| labels
+        .iter()
+        .position(|l| l == label)
+        .ok_or_else(|| move_error(move_op, format!("no stack {} (stacks are {})", label, labels.join(" "))));
+
+    let from = stack(&op.from)?;
+    let to = stack(&op.to)?;
+
+    let available = cargo_stacks[from].len();
+    if !lenient && op.n as usize > available {
+        return Err(move_error(move_op, format!("stack {} only holds {} crates", op.from, available)));
     }
 
+    Ok(StackMove { n: op.n as usize, from, to })
+}
+
+fn apply_move(labels: &[String], cargo_stacks: &mut [Vec<Crate>], move_op: &NumberedMove, crane: &mut dyn Crane, lenient: bool) -> Result<UndoMove, Error> {
+    let stack_move = resolve_move(labels, cargo_stacks, move_op, lenient)?;
+    crane
+        .apply(cargo_stacks, &stack_move)
+        .map_err(|reason| move_error(move_op, format!("crane {}: {}", crane.name(), reason)))
+}
+
+fn top_crates(cargo_stacks: &[Vec<Crate>]) -> String {
     let mut msg = String::new();
 
-    for stack in input.cargo_stacks {
-        if stack.len() > 0 {
-            msg.push(stack.last().map_or(' ', |c| *c));
+    for stack in cargo_stacks {
+        if let Some(c) = stack.last() {
+            msg.push_str(c);
         }
     }
 
-    Ok(msg)
+    msg
+}
+
+fn run_moves(input_path: &str, crane: &mut dyn Crane, lenient: bool) -> Result<String, Error> {
+    let mut input = read_input(input_path)?;
+    crane.start(&input.cargo_stacks);
+
+    for move_op in &input.operations {
+        apply_move(&input.labels, &mut input.cargo_stacks, move_op, crane, lenient)?;
+    }
+
+    Ok(top_crates(&input.cargo_stacks))
+}
+
+fn run_part(input_path: &str, crane: &mut dyn Crane) -> Result<String, Error> {
+    run_moves(input_path, crane, false)
 }
 
 pub fn part1(input_path: &str) -> Result<String, Error> {
-    run_part(input_path, move_stack_9000)
+    run_part(input_path, &mut Crane9000)
 }
 
 pub fn part2(input_path: &str) -> Result<String, Error> {
-    run_part(input_path, move_stack_9001)
-}
\ No newline at end of file
+    run_part(input_path, &mut Crane9001)
+}
+
+fn centered(text: &str, width: usize) -> String {
+    let left = (width - text.chars().count()) / 2;
+    format!("{:left$}{:<right$}", "", text, left = left, right = width - left)
+}
+
+// Draws the stacks the way the puzzle does: `[A]` cells, padded rows and a
+// label line. Each column is as wide as its widest crate or label, and at
+// least as wide as `[A]`, with both centered, so single letter crates over
+// 1..9 come out exactly as the puzzle.
+fn draw_stacks(labels: &[String], cargo_stacks: &[Vec<Crate>]) -> String {
+    let widths: Vec<usize> = labels
+        .iter()
+        .zip(cargo_stacks)
+        .map(|(label, stack)| stack
+            .iter()
+            .map(|c| c.chars().count() + 2)
+            .fold(label.chars().count().max(3), usize::max))
+        .collect();
+
+    let height = cargo_stacks.iter().map(|s| s.len()).max().unwrap_or(0);
+    let mut content = String::new();
+
+    for level in (0..height).rev() {
+        let cells: Vec<String> = cargo_stacks
+            .iter()
+            .zip(&widths)
+            .map(|(s, width)| centered(&s.get(level).map_or(String::new(), |c| format!("[{}]", c)), *width))
+            .collect();
+        content.push_str(&cells.join(" "));
+        content.push('\n');
+    }
+
+    let cells: Vec<String> = labels.iter().zip(&widths).map(|(label, width)| centered(label, *width)).collect();
+    content.push_str(&cells.join(" "));
+    content.push('\n');
+    content
+}
+
+const CLEAR_SCREEN: &str = "\x1b[2J\x1b[H";
+
+// Replays the moves and draws the stacks after every N moves. With --rewind
+// the moves are then undone from the undo log, back to the start. Frames are
+// printed, animated in the terminal, or written to a file.
+// Args: [input_path] [--crane NAME] [--lenient] [--rewind] [--every N] [--animate] [--delay MS] [--output FILE]
+pub fn replay(args: &[String]) -> Result<String, Error> {
+    let args = ToolArgs::parse(args, &["--animate", "--lenient", "--rewind"], &["--crane", "--every", "--delay", "--output"])?;
+    let path = args.positional_or(0, "puzzles/day5-input.txt");
+    let mut crane = crane_by_name(args.option("--crane").unwrap_or("9000"))?;
+    let every: usize = args.option_parsed("--every", 1)?;
+    let delay = Duration::from_millis(args.option_parsed("--delay", 200)?);
+    let animate = args.flag("--animate");
+
+    if every == 0 {
+        return Err(Error::General(String::from("Invalid value for --every: 0")));
+    }
+
+    let mut input = read_input(path)?;
+    let start = input.cargo_stacks.clone();
+    let total = input.operations.len();
+    let mut frames: Vec<String> = vec![format!("Start\n{}", draw_stacks(&input.labels, &input.cargo_stacks))];
+    let mut undo_log: Vec<UndoMove> = Vec::with_capacity(total);
+    crane.start(&input.cargo_stacks);
+
+    for (idx, move_op) in input.operations.iter().enumerate() {
+        undo_log.push(apply_move(&input.labels, &mut input.cargo_stacks, move_op, crane.as_mut(), args.flag("--lenient"))?);
+
+        let step = idx + 1;
+        if step % every == 0 || step == total {
+            frames.push(format!("Move {}/{}: {}\n{}", step, total, move_op.1, draw_stacks(&input.labels, &input.cargo_stacks)));
+        }
+    }
+
+    if args.flag("--rewind") {
+        for (idx, undo) in undo_log.iter().enumerate().rev() {
+            let move_op = &input.operations[idx];
+            undo.apply(&mut input.cargo_stacks).map_err(|reason| move_error(move_op, reason))?;
+
+            if idx % every == 0 {
+                frames.push(format!("Undo {}/{}: {}\n{}", idx + 1, total, move_op.1, draw_stacks(&input.labels, &input.cargo_stacks)));
+            }
+        }
+
+        if input.cargo_stacks != start {
+            return Err(Error::General(String::from("Rewinding the moves did not return to the starting layout")));
+        }
+    }
+
+    if let Some(output) = args.option("--output") {
+        std::fs::write(output, frames.join("\n")).map_err(Error::IOError)?;
+        return Ok(format!("Wrote {} frames to {}\n", frames.len(), output));
+    }
+
+    if animate {
+        for frame in &frames {
+            print!("{}{}", CLEAR_SCREEN, frame);
+            sleep(delay);
+        }
+        return Ok(String::new());
+    }
+
+    Ok(frames.join("\n"))
+}
+
+const CRATE_LETTERS: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ";
+
+fn random_token(rng: &mut Rng, alphabet: &[u8], max_len: i64) -> String {
+    (0..rng.range(1, max_len)).map(|_| *rng.choose(alphabet).unwrap() as char).collect()
+}
+
+// Runs the moves with the named crane and prints the top crates, followed by
+// whatever the crane reports. Moves are checked against the stacks unless
+// --lenient, which clamps them instead.
+// Args: [input_path] [--crane NAME] [--lenient]
+pub fn solve(args: &[String]) -> Result<String, Error> {
+    let args = ToolArgs::parse(args, &["--lenient"], &["--crane"])?;
+    let path = args.positional_or(0, "puzzles/day5-input.txt");
+    let mut crane = crane_by_name(args.option("--crane").unwrap_or("9000"))?;
+
+    let message = run_moves(path, crane.as_mut(), args.flag("--lenient"))?;
+    match crane.report() {
+        Some(report) => Ok(format!("{}\n{}\n", message, report)),
+        None => Ok(format!("{}\n", message))
+    }
+}
+
+// Reconstructs the starting layout: the input's drawing is taken as the state
+// after the moves, which are undone last to first with the crane's inverse.
+// Prints a complete input that gives that drawing when run with the crane.
+// Args: [input_path] [--crane NAME]
+pub fn reverse(args: &[String]) -> Result<String, Error> {
+    let args = ToolArgs::parse(args, &[], &["--crane"])?;
+    let path = args.positional_or(0, "puzzles/day5-input.txt");
+    let mut crane = crane_by_name(args.option("--crane").unwrap_or("9000"))?;
+
+    let mut input = read_input(path)?;
+    let end = input.cargo_stacks.clone();
+
+    for move_op in input.operations.iter().rev() {
+        let stack_move = resolve_move(&input.labels, &input.cargo_stacks, move_op, true)?;
+        crane
+            .inverse(&stack_move)
+            .apply(&mut input.cargo_stacks)
+            .map_err(|reason| move_error(move_op, format!("cannot undo with crane {}: {}", crane.name(), reason)))?;
+    }
+
+    let mut replayed = input.cargo_stacks.clone();
+    for move_op in &input.operations {
+        apply_move(&input.labels, &mut replayed, move_op, crane.as_mut(), false)?;
+    }
+    if replayed != end {
+        return Err(Error::General(format!("Crane {} does not reach the drawing from the reconstructed start", crane.name())));
+    }
+
+    Ok(input.to_string())
+}
+
+// Builds an input whose final top crates spell message. The final layout is
+// made up first, with the message on top of randomly chosen stacks over
+// up to max_move filler crates, and random moves of up to max_move crates are
+// undone from it with the crane's inverse.
+// Every undo takes crates that are there, so the moves are valid forwards.
+fn generate_input(message: &[Crate], stack_count: usize, move_count: usize, max_move: usize, crane: &dyn Crane, rng: &mut Rng) -> Input {
+    let mut stacks: Vec<usize> = (0..stack_count).collect();
+    rng.shuffle(&mut stacks);
+    stacks.truncate(message.len());
+    stacks.sort_unstable();
+
+    let mut cargo_stacks: CargoStacks = vec![Vec::new(); stack_count];
+    for (stack, top) in stacks.iter().zip(message) {
+        cargo_stacks[*stack] = (0..rng.below(max_move + 1)).map(|_| random_token(rng, CRATE_LETTERS, 1)).collect();
+        cargo_stacks[*stack].push(top.clone());
+    }
+
+    let mut moves: Vec<StackMove> = Vec::with_capacity(move_count);
+    while moves.len() < move_count {
+        let to = rng.below(stack_count);
+        let from = rng.below(stack_count);
+        if cargo_stacks[to].is_empty() || (from == to && stack_count > 1) {
+            continue;
+        }
+
+        let stack_move = StackMove { n: rng.range(1, cargo_stacks[to].len().min(max_move) as i64) as usize, from, to };
+        crane.inverse(&stack_move).apply(&mut cargo_stacks).unwrap();
+        moves.push(stack_move);
+    }
+
+    // The moves start after the drawing, its label line and the blank line.
+    let first_move_line = cargo_stacks.iter().map(|s| s.len()).max().unwrap_or(0) + 3;
+    let labels: Vec<String> = (1..=stack_count).map(|id| id.to_string()).collect();
+    let operations = moves
+        .iter()
+        .rev()
+        .enumerate()
+        .map(|(idx, m)| (first_move_line + idx, MoveOperation { n: m.n as u32, from: labels[m.from].clone(), to: labels[m.to].clone() }))
+        .collect();
+
+    Input { operations, labels, cargo_stacks }
+}
+
+// Writes a random input whose answer with the crane is message, then solves
+// it with run_part to check.
+// Args: <message> <output_path> [--stacks N] [--moves N] [--crane NAME] [--seed S]
+pub fn generate(args: &[String]) -> Result<String, Error> {
+    let args = ToolArgs::parse(args, &[], &["--stacks", "--moves", "--crane", "--seed"])?;
+    let (message, output) = match (args.positional(0), args.positional(1)) {
+        (Some(message), Some(output)) => (message, output),
+        _ => return Err(Error::General(String::from("Usage: 5 generate <message> <output_path> [--stacks N] [--moves N] [--crane NAME] [--seed S]")))
+    };
+    let crates: Vec<Crate> = message.chars().map(String::from).collect();
+    let stack_count: usize = args.option_parsed("--stacks", crates.len().max(9))?;
+    let move_count: usize = args.option_parsed("--moves", 500)?;
+    let crane_name = args.option("--crane").unwrap_or("9000");
+    let seed: u64 = args.option_parsed("--seed", Rng::seed_from_time())?;
+
+    if crates.is_empty() || !CRATE_RE.is_match(&format!("[{}]", message)) {
+        return Err(Error::General(format!("Invalid message: [{}] (crates can't be spaces or brackets)", message)));
+    }
+    if stack_count < crates.len() {
+        return Err(Error::General(format!("Need at least {} stacks for [{}], got {}", crates.len(), message, stack_count)));
+    }
+
+    let input = generate_input(&crates, stack_count, move_count, 5, crane_by_name(crane_name)?.as_ref(), &mut Rng::new(seed));
+    std::fs::write(output, input.to_string()).map_err(Error::IOError)?;
+
+    let answer = run_part(output, crane_by_name(crane_name)?.as_mut())?;
+    if answer != message {
+        return Err(Error::General(format!("Generated input {} gives [{}] instead of [{}] (seed {})", output, answer, message, seed)));
+    }
+
+    Ok(format!("Wrote {} with {} stacks and {} moves (seed {}); crane {} gives {}\n",
+        output, stack_count, move_count, seed, crane_name, answer))
+}
+
+type MoverFunction = fn(&mut [Vec<Crate>], &StackMove);
+type Timing = (String, f64);
+
+// Seconds per run of all moves over a fresh copy of the stacks, and the top
+// crates they leave. Copying the stacks isn't timed.
+fn time_moves(cargo_stacks: &CargoStacks, moves: &[StackMove], mover_fn: MoverFunction, iterations: u32) -> Timing {
+    let mut total = 0.0;
+    let mut result = String::new();
+
+    for _ in 0..iterations {
+        let mut stacks = cargo_stacks.clone();
+        let start = Instant::now();
+        for stack_move in moves {
+            mover_fn(&mut stacks, stack_move);
+        }
+        total += start.elapsed().as_secs_f64();
+        result = top_crates(&stacks);
+    }
+    (result, total / iterations as f64)
+}
+
+// Times the bulk movers against the original crate by crate ones on a
+// generated input.
+// Args: [--moves N] [--stacks N] [--max-move N] [--iterations N] [--seed S]
+pub fn bench(args: &[String]) -> Result<String, Error> {
+    let args = ToolArgs::parse(args, &[], &["--moves", "--stacks", "--max-move", "--iterations", "--seed"])?;
+    let move_count: usize = args.option_parsed("--moves", 1_000_000)?;
+    let stack_count: usize = args.option_parsed("--stacks", 9)?.max(1);
+    let max_move: usize = args.option_parsed("--max-move", 100)?.max(1);
+    let iterations: u32 = args.option_parsed("--iterations", 3)?.max(1);
+    let seed: u64 = args.option_parsed("--seed", Rng::seed_from_time())?;
+
+    let mut rng = Rng::new(seed);
+    let message: Vec<Crate> = (0..stack_count).map(|_| random_token(&mut rng, CRATE_LETTERS, 1)).collect();
+    let input = generate_input(&message, stack_count, move_count, max_move, &Crane9000, &mut rng);
+
+    // Stack heights only depend on the counts, so the moves are valid for both cranes.
+    let moves = input.operations
+        .iter()
+        .map(|move_op| resolve_move(&input.labels, &input.cargo_stacks, move_op, true))
+        .collect::<Result<Vec<StackMove>, Error>>()?;
+    let crates_moved: usize = moves.iter().map(|m| m.n).sum();
+
+    let runs: Vec<(&str, Timing, Timing)> = vec![
+        ("9000",
+            time_moves(&input.cargo_stacks, &moves, move_stack_9000, iterations),
+            time_moves(&input.cargo_stacks, &moves, popping::move_stack_9000, iterations)),
+        ("9001",
+            time_moves(&input.cargo_stacks, &moves, move_stack_9001, iterations),
+            time_moves(&input.cargo_stacks, &moves, popping::move_stack_9001, iterations)),
+    ];
+
+    let mut content = format!("{} moves of up to {} crates ({} crates moved) over {} stacks, {} iterations (seed {})\n",
+        moves.len(), max_move, crates_moved, stack_count, iterations, seed);
+    content.push_str(&format!("{:<6} {:>14} {:>14} {:>9}\n", "crane", "bulk (ms)", "popping (ms)", "speedup"));
+
+    for (name, (bulk_result, bulk_secs), (popping_result, popping_secs)) in runs {
+        if bulk_result != popping_result {
+            return Err(Error::General(format!(
+                "Movers disagree on {}: bulk {} vs popping {}", name, bulk_result, popping_result)));
+        }
+        content.push_str(&format!("{:<6} {:>14.2} {:>14.2} {:>8.1}x\n",
+            name, bulk_secs * 1000.0, popping_secs * 1000.0, popping_secs / bulk_secs));
+    }
+
+    Ok(content)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::normalize::normalize;
+    use crate::common::random::check_property;
+
+    fn parse_text(content: &str) -> Result<Input, Error> {
+        let (lines, _) = normalize(content, &INPUT_NORMALIZATION);
+        parse_input(&lines)
+    }
+
+    // Numbers moves by the lines they get when printed under the drawing of
+    // cargo_stacks, after its label line and the blank line.
+    fn numbered_moves(cargo_stacks: &[Vec<Crate>], operations: Vec<MoveOperation>) -> Vec<NumberedMove> {
+        let first_move_line = cargo_stacks.iter().map(|s| s.len()).max().unwrap_or(0) + 3;
+        operations
+            .into_iter()
+            .enumerate()
+            .map(|(idx, op)| (first_move_line + idx, op))
+            .collect()
+    }
+
+    // Half of the layouts use the puzzle's 1..N labels, the others random tokens.
+    fn random_input(rng: &mut Rng) -> Input {
+        let stack_count = rng.range(1, 12) as usize;
+
+        let mut labels: Vec<String> = Vec::new();
+        if rng.chance(1, 2) {
+            labels.extend((1..=stack_count).map(|id| id.to_string()));
+        }
+        while labels.len() < stack_count {
+            let label = random_token(rng, b"abcxyz0123456789", 4);
+            if !labels.contains(&label) {
+                labels.push(label);
+            }
+        }
+
+        let cargo_stacks: CargoStacks = (0..stack_count)
+            .map(|_| (0..rng.below(12)).map(|_| random_token(rng, CRATE_LETTERS, 3)).collect())
+            .collect();
+        let operations = (0..rng.below(6))
+            .map(|_| MoveOperation {
+                n: rng.range(1, 5) as u32,
+                from: rng.choose(&labels).unwrap().clone(),
+                to: rng.choose(&labels).unwrap().clone(),
+            })
+            .collect();
+
+        Input { operations: numbered_moves(&cargo_stacks, operations), labels, cargo_stacks }
+    }
+
+    // Printing an input and parsing it again gives the same stacks and moves,
+    // over randomly generated layouts.
+    #[test]
+    fn drawing_round_trip() {
+        check_property("Drawing round trip", 500, |rng| {
+            let input = random_input(rng);
+            let text = input.to_string();
+
+            match parse_text(&text) {
+                Err(e) => Err(format!("{:?}\n{}", e, text)),
+                Ok(parsed) if parsed != input => Err(format!("\n{}\nparsed back as:\n{}", text, parsed)),
+                Ok(_) => Ok(())
+            }
+        });
+    }
+
+    #[test]
+    fn metrics_peak_includes_starting_layout() {
+        let stacks = |heights: &[usize]| -> CargoStacks {
+            heights.iter().map(|h| vec![String::from("A"); *h]).collect()
+        };
+
+        let mut crane = crane_by_name("metrics:9000").unwrap();
+        crane.start(&stacks(&[4, 1]));
+        assert_eq!(crane.report().unwrap(), "lifts: 0, crates moved: 0, peak height: 4");
+
+        let mut cargo_stacks = stacks(&[4, 1]);
+        crane.apply(&mut cargo_stacks, &StackMove { n: 3, from: 0, to: 1 }).unwrap();
+        assert_eq!(crane.report().unwrap(), "lifts: 3, crates moved: 3, peak height: 4");
+    }
+
+    const SAMPLE_DRAWING: &str = "    [D]    \n[N] [C]    \n[Z] [M] [P]\n 1   2   3 \n\n";
+
+    // Runs the moves after SAMPLE_DRAWING, whose first move is on line 6.
+    fn run_sample(moves: &str, lenient: bool) -> Result<String, String> {
+        let mut input = parse_text(&format!("{}{}", SAMPLE_DRAWING, moves)).unwrap();
+        for move_op in &input.operations {
+            apply_move(&input.labels, &mut input.cargo_stacks, move_op, &mut Crane9000, lenient)
+                .map_err(|e| format!("{:?}", e))?;
+        }
+        Ok(top_crates(&input.cargo_stacks))
+    }
+
+    #[test]
+    fn moves_need_known_stacks() {
+        assert_eq!(run_sample("move 1 from 2 to 1\nmove 1 from 4 to 1\n", false).unwrap_err(),
+            "General(\"Invalid move on line 7: [move 1 from 4 to 1]: no stack 4 (stacks are 1 2 3)\")");
+        assert_eq!(run_sample("move 1 from 1 to 9\n", true).unwrap_err(),
+            "General(\"Invalid move on line 6: [move 1 from 1 to 9]: no stack 9 (stacks are 1 2 3)\")");
+    }
+
+    #[test]
+    fn moves_need_enough_crates() {
+        assert_eq!(run_sample("move 1 from 2 to 1\nmove 1 from 1 to 2\nmove 4 from 2 to 3\n", false).unwrap_err(),
+            "General(\"Invalid move on line 8: [move 4 from 2 to 3]: stack 2 only holds 3 crates\")");
+    }
+
+    #[test]
+    fn lenient_moves_are_clamped() {
+        assert_eq!(run_sample("move 5 from 1 to 3\n", true).unwrap(), "DZ");
+        assert_eq!(run_sample("move 5 from 1 to 3\nmove 1 from 1 to 2\n", true).unwrap(), "DZ");
+    }
+
+    // Undoing random moves last to first gives back the starting layout, for
+    // every kind of crane.
+    #[test]
+    fn undo_restores_start() {
+        for name in ["9000", "9001", "limited:2", "limited:3"] {
+            check_property(&format!("Undo with crane {}", name), 100, |rng| {
+                let mut crane = crane_by_name(name).unwrap();
+                let stack_count = rng.range(1, 6) as usize;
+                let start: CargoStacks = (0..stack_count)
+                    .map(|_| (0..rng.below(8)).map(|_| random_token(rng, CRATE_LETTERS, 1)).collect())
+                    .collect();
+
+                let mut cargo_stacks = start.clone();
+                let mut undo_log: Vec<UndoMove> = Vec::new();
+                for _ in 0..rng.below(30) {
+                    let op = StackMove { n: rng.range(1, 6) as usize, from: rng.below(stack_count), to: rng.below(stack_count) };
+                    undo_log.push(crane.apply(&mut cargo_stacks, &op)?);
+                }
+                for undo in undo_log.iter().rev() {
+                    undo.apply(&mut cargo_stacks)?;
+                }
+
+                if cargo_stacks == start {
+                    Ok(())
+                }
+                else {
+                    Err(format!("started from {:?}, ended with {:?}", start, cargo_stacks))
+                }
+            });
+        }
+    }
+}
@@ -36,7 +36,7 @@ fn get_input(input_path: &str) -> Result<String, Error> {
     let mut lines = read_non_empty_lines(input_path)?;
     match lines.pop() {
         Some(line) => Ok(line),
-        None => Err(Error::General(format!("Invalid input.")))
+        None => Err(Error::General("Invalid input.".to_string()))
     }
 }
 
@@ -45,7 +45,7 @@ pub fn run_part(input_path: &str, end_packet_len: usize) -> Result<String, Error
     let chars: Vec<char> = line.chars().collect();
 
     match get_start_of_packet(chars, end_packet_len) {
-        None => Err(Error::General(format!("Could not find answer."))),
+        None => Err(Error::General("Could not find answer.".to_string())),
         Some(n) => Ok(n.to_string()) 
     }
 }
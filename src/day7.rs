@@ -1,6 +1,7 @@
 use std::collections::HashMap;
 
-use crate::common::{Error, read_non_empty_lines};
+use crate::common::{Error, log, read_non_empty_lines};
+use crate::common::parser::{alt, integer, map, pair, parse_all, preceded, tag, terminated, token, whitespace};
 
 #[derive(Debug)]
 pub struct DirectoryNode {
@@ -19,7 +20,7 @@ pub struct FileNode {
 impl DirectoryNode {
     fn new(name: String) -> DirectoryNode {
         DirectoryNode {
-            name: name,
+            name,
             files: HashMap::new(),
             directories: HashMap::new(),
         }
@@ -45,8 +46,8 @@ impl DirectoryNode {
 impl FileNode {
     fn new(name: String, size: u32) -> FileNode {
         FileNode {
-            name: name,
-            size: size,
+            name,
+            size,
         }
     }
 }
@@ -64,7 +65,7 @@ fn process_cd(root: &mut DirectoryNode, current_path: &mut Vec<String>, d_name:
         let current_dir = root.get_dir(current_path).unwrap();
         match current_dir.directories.get(d_name) {
             None => {
-                println!("[WARNING] Not sub directory: {}", d_name);
+                log::warning!("Not sub directory: {}", d_name);
             },
             Some(_) => {
                 current_path.push(String::from(d_name));
@@ -75,71 +76,82 @@ fn process_cd(root: &mut DirectoryNode, current_path: &mut Vec<String>, d_name:
     Ok(())
 }
 
+// A size of None is a directory entry.
 fn process_ls_output_line(
     root: &mut DirectoryNode,
     current_path: &Vec<String>,
-    entry_info: &str,
+    size: Option<u32>,
     entry_name: &str)
     -> Result<(), Error>
 {
     let current_dir = root.get_dir(current_path).unwrap();
 
-    if entry_info == "dir" {
-        if !current_dir.directories.contains_key(entry_name) {
-            let d = DirectoryNode::new(String::from(entry_name));
-            current_dir.directories.insert(String::from(entry_name), d);
-        }
-    }
-    else {
-        match entry_info.parse::<u32>() {
-            Err(_) => {
-                return Err(Error::General(format!("Invalid output entyr for ls: {}", entry_info)));
-            },
-            Ok(size) => {
-                let f = FileNode::new(String::from(entry_name), size);
-                current_dir.files.insert(String::from(entry_name), f);
+    match size {
+        None => {
+            if !current_dir.directories.contains_key(entry_name) {
+                let d = DirectoryNode::new(String::from(entry_name));
+                current_dir.directories.insert(String::from(entry_name), d);
             }
+        },
+        Some(size) => {
+            let f = FileNode::new(String::from(entry_name), size);
+            current_dir.files.insert(String::from(entry_name), f);
         }
-
     }
 
     Ok(())
 }
 
-fn parse_lines(lines: &Vec<String>) -> Result<DirectoryNode, Error> {
+#[derive(Debug)]
+enum TerminalLine<'a> {
+    Cd(&'a str),
+    Ls,
+    Dir(&'a str),
+    File(u32, &'a str),
+}
+
+fn parse_terminal_line(line: &str) -> Result<TerminalLine<'_>, Error> {
+    // Commands are parsed on their own so an unknown one isn't taken for ls output.
+    if let Some(command_line) = line.strip_prefix('$') {
+        let command = |name| preceded(pair(tag("$"), whitespace()), tag(name));
+
+        return parse_all(alt((
+            map(preceded(command("cd"), preceded(whitespace(), token())), TerminalLine::Cd),
+            map(command("ls"), |_| TerminalLine::Ls),
+        )), line).map_err(|_| Error::General(format!(
+            "Unknown command: {}", command_line.split_whitespace().next().unwrap_or(""))));
+    }
+
+    parse_all(alt((
+        map(preceded(pair(tag("dir"), whitespace()), token()), TerminalLine::Dir),
+        map(pair(terminated(integer::<u32>(), whitespace()), token()), |(size, name)| TerminalLine::File(size, name)),
+    )), line)
+}
+
+fn parse_lines(lines: &[String]) -> Result<DirectoryNode, Error> {
     let mut root = DirectoryNode::new(String::from(""));
     let mut current_path: Vec<String> = Vec::new();
-    let mut last_command: String = String::from("");
+    let mut in_ls: bool = false;
 
-    for line in lines {
-        let line_parts = line.split(" ").collect::<Vec<&str>>();
-        
-        // Process command.
-        if line_parts.len() >= 2 && line_parts[0] == "$" {
-            last_command = String::from(line_parts[1]);
+    for (idx, line) in lines.iter().enumerate() {
+        let _line_context = log::line_context(idx + 1);
 
-            if line_parts.len() >= 3 && line_parts[1] == "cd" {
-                process_cd(&mut root, &mut current_path, &line_parts[2])?;
-            }
-            else if line_parts[1] == "ls" {
-                // Nothing to do, just need to know in ls command.
-            }
-            else {
-                return Err(Error::General(format!("Unknown command: {}", line_parts[1])));
-            }
-        }
-        // Process out put lines.
-        else {
-            if last_command == "ls" {
-                process_ls_output_line(
-                    &mut root,
-                    &current_path,
-                    line_parts[0],
-                    line_parts[1]
-                )?;
-            }
-            else {
-                return Err(Error::General(format!("Can't process output from command: {}", last_command)));
+        match parse_terminal_line(line)? {
+            TerminalLine::Cd(d_name) => {
+                in_ls = false;
+                process_cd(&mut root, &mut current_path, d_name)?;
+            },
+            TerminalLine::Ls => {
+                in_ls = true;
+            },
+            TerminalLine::Dir(entry_name) | TerminalLine::File(_, entry_name) if !in_ls => {
+                return Err(Error::General(format!("Output outside of ls for entry: {}", entry_name)));
+            },
+            TerminalLine::Dir(entry_name) => {
+                process_ls_output_line(&mut root, &current_path, None, entry_name)?;
+            },
+            TerminalLine::File(size, entry_name) => {
+                process_ls_output_line(&mut root, &current_path, Some(size), entry_name)?;
             }
         }
     }
@@ -186,7 +198,7 @@ fn calculate_part1_result(root: &DirectoryNode) -> u32 {
 
     process_directories::<u32>(root, &mut result, |state, _path, total_size| {
         if total_size <= 100000 {
-            *state = *state + total_size;
+            *state += total_size;
         }
     });
 
@@ -197,7 +209,7 @@ fn get_root_size(root: &DirectoryNode) -> u32 {
     let mut result: u32 = 0;
 
     process_directories::<u32>(root, &mut result, |state, path, total_size| {
-        if path.len() == 0 {
+        if path.is_empty() {
             *state = total_size;
         }
     });
@@ -239,13 +251,42 @@ pub fn part2(input_path: &str) -> Result<String, Error> {
     };
 
     process_directories::<Part2State>(&root, &mut state, |state, path, total_size| {
-        if total_size >= state.need_to_free {
-            if state.dir_to_delete.is_none() || state.dir_to_delete_size.unwrap() > total_size {
+        if total_size >= state.need_to_free
+            && (state.dir_to_delete.is_none() || state.dir_to_delete_size.unwrap() > total_size) {
                 state.dir_to_delete = Some(path.join("/"));
                 state.dir_to_delete_size = Some(total_size);
             }
-        }
     });
 
     Ok(format!("{}", state.dir_to_delete_size.unwrap()))
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn error_message(transcript: &[&str]) -> String {
+        let lines: Vec<String> = transcript.iter().map(|l| l.to_string()).collect();
+        match parse_lines(&lines) {
+            Err(Error::General(message)) => message,
+            other => panic!("Expected a general error, got {:?}", other)
+        }
+    }
+
+    #[test]
+    fn unknown_commands_are_command_errors() {
+        assert_eq!(error_message(&["$ cd /", "$ frob"]), "Unknown command: frob");
+        assert_eq!(error_message(&["$ cd /", "$ cd"]), "Unknown command: cd");
+        assert_eq!(error_message(&["$ cd /", "$ ls", "$ frob x"]), "Unknown command: frob");
+    }
+
+    #[test]
+    fn ls_output() {
+        let lines: Vec<String> = ["$ cd /", "$ ls", "dir a", "120 b.txt"].iter().map(|l| l.to_string()).collect();
+        let root = parse_lines(&lines).unwrap();
+        assert!(root.directories.contains_key("a"));
+        assert_eq!(root.files["b.txt"].size, 120);
+
+        let lines: Vec<String> = ["$ cd /", "$ ls", "big b.txt"].iter().map(|l| l.to_string()).collect();
+        assert!(parse_lines(&lines).is_err());
+    }
+}
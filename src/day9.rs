@@ -1,58 +1,16 @@
-use crate::common::{Error, read_non_empty_lines};
-use regex::Regex;
+use crate::common::{Direction, Error, Point, Vec2, read_non_empty_lines};
+use crate::common::regex_parse::regex_struct;
 use std::collections::HashSet;
-use lazy_static::lazy_static;
 
-#[derive(Debug)]
-enum Direction {
-    Up,
-    Down,
-    Left,
-    Right
-}
-
-impl Direction {
-    fn from(s: &str) -> Result<Direction, Error> {
-        match s {
-            "U" => Ok(Direction::Up),
-            "D" => Ok(Direction::Down),
-            "L" => Ok(Direction::Left),
-            "R" => Ok(Direction::Right),
-            _ => Err(Error::General(format!("Invalid direction: [{}]", s)))
-        }
+regex_struct! {
+    #[derive(Debug)]
+    struct Movement = r"([UDLR]) (\d+)" {
+        direction: Direction,
+        amount: i32,
     }
-
-    fn get_movement_vector(self: &Direction) -> (i32, i32) {
-        match self {
-            Direction::Up => (0, 1),
-            Direction::Down => (0, -1),
-            Direction::Left => (-1, 0),
-            Direction::Right => (1, 0),
-        }
-    }
-}
-
-
-#[derive(Debug)]
-struct Movement {
-    direction: Direction,
-    amount: i32,
 }
 
 impl Movement {
-    fn from(line: &str) -> Result<Movement, Error> {
-        lazy_static! { static ref LINE_RE: Regex = Regex::new(r"([UDLR]) (\d+)").unwrap(); }
-        match LINE_RE.captures(line) {
-            None => Err(Error::General(format!("Invalid line: [{}]", line))),
-            Some(cap) => {
-                Ok(Movement {
-                    direction: Direction::from(cap.get(1).unwrap().as_str())?,
-                    amount: cap.get(2).unwrap().as_str().parse::<i32>().unwrap()
-                })
-            }
-        }
-    }
-
     fn parse_input(input_path: &str) -> Result<Vec<Movement>, Error> {
         let lines = read_non_empty_lines(input_path)?;
     
@@ -66,8 +24,8 @@ impl Movement {
 }
 
 struct RopePositions {
-    positions: Vec<(i32, i32)>,
-    tail_visited: HashSet<(i32, i32)>,
+    positions: Vec<Point>,
+    tail_visited: HashSet<Point>,
 }
 
 impl RopePositions {
@@ -77,10 +35,10 @@ impl RopePositions {
             tail_visited: HashSet::new(),
         };
         for _ in 0..knot_size {
-            _self.positions.push((0, 0));
+            _self.positions.push(Point::ORIGIN);
         }
-        if _self.positions.len() > 0 {
-            _self.tail_visited.insert((0, 0));
+        if !_self.positions.is_empty() {
+            _self.tail_visited.insert(Point::ORIGIN);
         }
         _self
     }
@@ -92,29 +50,24 @@ impl RopePositions {
     }
 
     fn mv(self: &mut RopePositions, movement: &Movement) {
-        if self.positions.len() == 0 {
+        if self.positions.is_empty() {
             return;
         }
 
-        let move_vec = movement.direction.get_movement_vector();
+        let move_vec: Vec2 = movement.direction.get_movement_vector();
         for _ in 0..movement.amount {
-            let head_pos = self.positions.get(0).unwrap();
-            self.positions[0] = (head_pos.0 + move_vec.0, head_pos.1 + move_vec.1);
+            self.positions[0] += move_vec;
 
             for pos in 1..self.positions.len() {
-                let tail_pos = &self.positions[pos];
-                let head_pos = &self.positions[pos - 1];
-
-                let xd = head_pos.0 - tail_pos.0;
-                let yd = head_pos.1 - tail_pos.1;
+                let tail_pos = self.positions[pos];
+                let head_pos = self.positions[pos - 1];
 
-                if xd.abs() > 1 || yd.abs() > 1 {
-                    let new_pos = (tail_pos.0 + xd.signum(), tail_pos.1 + yd.signum());
-                    self.positions[pos] = new_pos;
+                if head_pos.chebyshev(tail_pos) > 1 {
+                    self.positions[pos] = tail_pos + (head_pos - tail_pos).signum();
                 }
             }
 
-            self.tail_visited.insert(*self.positions.get(self.positions.len() - 1).unwrap());
+            self.tail_visited.insert(*self.positions.last().unwrap());
         }
     }
 }
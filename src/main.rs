@@ -13,11 +13,103 @@ mod day8;
 mod day9;
 mod day10;
 
-use common::Error;
+use common::{Error, log};
+use common::log::Level;
 
 type ProblemFunction = fn(&str) -> Result<String, Error>;
+type ToolFunction = fn(&[String]) -> Result<String, Error>;
+
+const USAGE: &str = "Usage: aoc2022 [-v | --verbose]... [-q | --quiet] [<day> <tool> [args...]]";
+
+struct Args {
+    level: Level,
+    // Day, tool name and the tool's own arguments.
+    tool: Option<(String, String, Vec<String>)>,
+}
+
+fn parse_args() -> Result<Args, String> {
+    let mut level = Level::Warning;
+    let mut positional: Vec<String> = Vec::new();
+
+    for arg in std::env::args().skip(1) {
+        if !positional.is_empty() {
+            positional.push(arg);
+            continue;
+        }
+        match arg.as_str() {
+            "-v" | "--verbose" => level = level.more_verbose(),
+            "-q" | "--quiet" => level = Level::Error,
+            _ if arg.starts_with('-') => return Err(format!("Unknown argument: {}", arg)),
+            _ => positional.push(arg)
+        }
+    }
+
+    let tool = match positional.len() {
+        0 => None,
+        1 => return Err(format!("Missing tool name for day {}", positional[0])),
+        _ => {
+            let rest = positional.split_off(2);
+            Some((positional[0].clone(), positional[1].clone(), rest))
+        }
+    };
+
+    Ok(Args { level, tool })
+}
+
+fn run_tool(day_name: &str, tool_name: &str, args: &[String]) -> Result<String, Error> {
+    let tools: Vec<(&str, &str, ToolFunction)> = vec![
+        ("1", "stats", day1::stats),
+        ("2", "play", day2::play),
+        ("2", "analyze", day2::analyze),
+        ("3", "solve", day3::solve),
+        ("3", "bench", day3::bench),
+        ("4", "coverage", day4::coverage),
+        ("4", "groups", day4::groups),
+        ("4", "render", day4::render),
+        ("5", "replay", day5::replay),
+        ("5", "solve", day5::solve),
+        ("5", "reverse", day5::reverse),
+        ("5", "generate", day5::generate),
+        ("5", "bench", day5::bench),
+    ];
+
+    match tools.iter().find(|(day, name, _)| *day == day_name && *name == tool_name) {
+        Some((_, _, t_func)) => {
+            let _context = log::solver_context(day_name, tool_name);
+            t_func(args)
+        },
+        None => {
+            let available: Vec<String> = tools
+                .iter()
+                .map(|(day, name, _)| format!("{} {}", day, name))
+                .collect();
+            Err(Error::General(format!(
+                "Unknown tool: {} {} (available: {})", day_name, tool_name, available.join(", "))))
+        }
+    }
+}
 
 fn main() {
+    let args = match parse_args() {
+        Ok(args) => args,
+        Err(msg) => {
+            eprintln!("{}\n{}", msg, USAGE);
+            std::process::exit(2);
+        }
+    };
+    log::set_level(args.level);
+
+    if let Some((day_name, tool_name, tool_args)) = args.tool {
+        match run_tool(&day_name, &tool_name, &tool_args) {
+            Ok(output) => print!("{}", output),
+            Err(e) => {
+                log::error!("{:?}", e);
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+
     let problems: Vec<(&str, &str, &str, ProblemFunction)> = vec![
         ("1", "1", "puzzles/day1-input.txt", day1::part1),
         ("1", "2", "puzzles/day1-input.txt", day1::part2),
@@ -43,6 +135,9 @@ fn main() {
 
     println!("Status,Day,Part,Timing,Answer");
     for (day_name, part_name, file_name, p_func) in problems {
+        let _context = log::solver_context(day_name, part_name);
+        log::info!("Reading {}", file_name);
+
         let start = Instant::now();
         let result = catch_unwind(|| { p_func(file_name) });
 